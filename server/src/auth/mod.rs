@@ -4,7 +4,9 @@ use axum::extract::{FromRequestParts, Query};
 use axum::http::request::Parts;
 use axum::Extension;
 use hyper::http;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{
+    decode, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, Validation,
+};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
@@ -37,6 +39,19 @@ pub struct Authed {
     pub sub: String,
     pub company: String,
     pub exp: usize,
+    /// Watches the poll without taking part, see `spectator_token_gen`.
+    #[serde(default)]
+    pub spectator: bool,
+}
+
+/// Claims of an invite link, see `invite_token_gen`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteClaims {
+    pub poll_id: String,
+    pub invite_id: String,
+    /// Name given to whoever joins with the invite, if set.
+    pub name: Option<String>,
+    pub exp: usize,
 }
 
 impl Authed {
@@ -66,10 +81,10 @@ where
             };
 
             let Some(token) = token_o else {
-				return Err(Error::MissingCredentials)
-			};
+                return Err(Error::MissingCredentials);
+            };
 
-            return verify(token)
+            return verify(token);
         };
 
         Ok(authed.clone())
@@ -96,11 +111,11 @@ where
         let Some(tokened) = already_tokened else {
             let token_o = extract_token(parts, state).await;
             let Some(token) = token_o else {
-                return Err(Error::WrongCredentials)
+                return Err(Error::WrongCredentials);
             };
             let tokened = Self { token };
             parts.extensions.insert(tokened.clone());
-            return Ok(tokened)
+            return Ok(tokened);
         };
 
         Ok(tokened.clone())
@@ -144,18 +159,63 @@ pub fn token_gen(
     user_id: String,
     name: String,
     expire_time: usize,
+) -> Result<String, Error> {
+    sign(&claims(poll_id, user_id, name, expire_time))
+}
+
+/// Token of a spectator, who receives what the room does but never joins
+/// the poll.
+pub fn spectator_token_gen(
+    poll_id: String,
+    user_id: String,
+    name: String,
+    expire_time: usize,
 ) -> Result<String, Error> {
     let claims = Authed {
+        spectator: true,
+        ..claims(poll_id, user_id, name, expire_time)
+    };
+    sign(&claims)
+}
+
+fn claims(poll_id: String, user_id: String, name: String, expire_time: usize) -> Authed {
+    Authed {
         poll_id,
         name,
         sub: user_id,
         company: "Ranker Inc.".to_string(),
         exp: (chrono::Utc::now().timestamp() + expire_time as i64) as usize,
-    };
-    let token =
-        encode(&Header::default(), &claims, &KEYS.encoding).map_err(|_| Error::TokenCreation)?;
+        spectator: false,
+    }
+}
+
+/// Token of an invite link, which only the store can tell is revoked or
+/// used up.
+pub fn invite_token_gen(
+    poll_id: String,
+    invite_id: String,
+    name: Option<String>,
+    expire_time: usize,
+) -> Result<String, Error> {
+    sign(&InviteClaims {
+        poll_id,
+        invite_id,
+        name,
+        exp: (chrono::Utc::now().timestamp() + expire_time as i64) as usize,
+    })
+}
+
+pub fn verify_invite(token: String) -> Result<InviteClaims, Error> {
+    decode::<InviteClaims>(&token, &KEYS.decoding, &Validation::default())
+        .map(|token_data| token_data.claims)
+        .map_err(|err| match err.kind() {
+            ErrorKind::ExpiredSignature => Error::InviteExpired,
+            _ => Error::InvalidToken,
+        })
+}
 
-    Ok(token)
+fn sign<T: Serialize>(claims: &T) -> Result<String, Error> {
+    encode(&Header::default(), claims, &KEYS.encoding).map_err(|_| Error::TokenCreation)
 }
 
 pub fn verify(token: String) -> Result<Authed, Error> {
@@ -3,13 +3,16 @@ use axum::{
     Extension, Router,
 };
 use server::{
-    data::redis::{polls::POLL_KEY_PREFIX, redis_keyspace_notifications},
-    handlers::not_found,
+    data::{
+        embedded::EmbeddedPollStore, memory::MemoryPollStore, redis::RedisPollStore, DynPollStore,
+    },
+    handlers::{not_found, schedule},
     models::room::Rooms,
     services::{polls, sse, ws},
-    state::{AppState, EnvConfig},
+    shared::throttle::Throttle,
+    state::{AppState, EnvConfig, StoreKind},
 };
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::{signal, sync::broadcast};
 use tower::ServiceBuilder;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
@@ -21,20 +24,30 @@ async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
     let config = envy::prefixed("RANKER_").from_env::<EnvConfig>()?;
 
-    let redis_url = config.redis_url.clone();
-    let client = redis::Client::open(redis_url)?;
-    let redis_mgr = redis::aio::ConnectionManager::new(client.clone()).await?;
+    let store: DynPollStore = match config.store {
+        StoreKind::Redis => {
+            let redis_url = config.redis_url.clone();
+            let client = redis::Client::open(redis_url)?;
+            Arc::new(RedisPollStore::new(client).await?)
+        }
+        StoreKind::Memory => Arc::new(MemoryPollStore::new()),
+        StoreKind::Embedded => Arc::new(EmbeddedPollStore::open(&config.embedded_path)?),
+    };
 
-    let middleware_stack = ServiceBuilder::new().layer(Extension(redis_mgr));
+    let middleware_stack = ServiceBuilder::new().layer(Extension(store.clone()));
 
     let (notify_tx, _rx) = broadcast::channel(100);
     let (sse_tx, _rx) = broadcast::channel(100);
     let rooms = Arc::new(Rooms::default());
+    let scheduler = schedule::run(store.clone(), rooms.clone());
     let app_state = Arc::new(AppState {
         env: config.clone(),
         rooms: rooms.clone(),
         notify_tx: notify_tx.clone(),
         sse_tx: sse_tx.clone(),
+        join_throttle: Arc::new(Throttle::new(Duration::from_secs(
+            config.join_failure_window,
+        ))),
     });
 
     let client_allow_origin = format!("{}:{}", config.client_domain, config.client_port);
@@ -100,11 +113,11 @@ async fn main() -> anyhow::Result<()> {
         );
 
     let notifier = async {
-        redis_keyspace_notifications(client, |mut key| {
-            let key = key.split_off(POLL_KEY_PREFIX.len());
-            let _ = notify_tx.send(key);
-        })
-        .await?;
+        store
+            .watch_removed(Box::new(move |poll_id| {
+                let _ = notify_tx.send(poll_id);
+            }))
+            .await?;
 
         Ok(()) as anyhow::Result<()>
     };
@@ -128,6 +141,9 @@ async fn main() -> anyhow::Result<()> {
         res = notifier => {
             Ok(res?)
         }
+        res = scheduler => {
+            Ok(res?)
+        }
     }
 }
 
@@ -0,0 +1,520 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+
+use crate::{
+    data::PollStore,
+    errors::Error,
+    models::{
+        AllocationList, ApprovalList, BallotType, Estimation, Invite, InviteID, Nomination,
+        NominationID, Poll, PollAction, PollKind, PollPhase, RankingList, Results, ScoreList,
+    },
+    scoring::{summarize, TallyReport},
+};
+
+/// How often expired polls are swept out of the store.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Backend that keeps each poll as a single document and applies changes in
+/// process.
+///
+/// `PollStore` is implemented on top of it, so every in-process backend
+/// enforces the same rules as the Lua scripts of the Redis backend.
+pub trait DocumentStore: Clone + Send + Sync + 'static {
+    /// Whether calls block the thread on disk I/O, in which case `PollStore`
+    /// runs them on tokio's blocking pool.
+    const BLOCKING: bool = false;
+
+    fn insert(&self, ttl: usize, poll: Poll) -> Result<(), Error>;
+
+    /// Reads the poll without writing anything. Expired polls are reported
+    /// as not found.
+    fn get(&self, poll_id: &str) -> Result<Poll, Error>;
+
+    /// Applies `f` to the poll and returns the updated poll. Nothing is
+    /// written if `f` fails, and expired polls are reported as not found.
+    fn update<F>(&self, poll_id: &str, f: F) -> Result<Poll, Error>
+    where
+        F: FnOnce(&mut Poll) -> Result<(), Error>;
+
+    /// Deletes the poll, notifying `removed_tx` if it existed.
+    fn remove(&self, poll_id: &str) -> Result<(), Error>;
+
+    /// Ids and due times of the polls in `schedule`, read from its index
+    /// rather than from every poll. Expired polls are left out.
+    fn scheduled(&self, schedule: Schedule) -> Result<Vec<(String, i64)>, Error>;
+
+    /// Deletes every expired poll, notifying `removed_tx` for each.
+    fn remove_expired(&self) -> Result<(), Error>;
+
+    fn removed_tx(&self) -> &broadcast::Sender<String>;
+}
+
+/// Polls waiting for something to happen at a given time, indexed by each
+/// `DocumentStore` as it writes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Schedule {
+    /// `Poll.voting_starts_at` of the polls voting has not started in yet.
+    VotingStarts,
+    /// `Poll.closes_at` of the polls being voted on.
+    Deadlines,
+}
+
+impl Schedule {
+    pub const ALL: [Schedule; 2] = [Schedule::VotingStarts, Schedule::Deadlines];
+
+    /// When the poll is due, if it belongs in this schedule at all.
+    pub fn due_at(&self, poll: &Poll) -> Option<i64> {
+        match self {
+            Schedule::VotingStarts => match poll.phase {
+                PollPhase::Lobby | PollPhase::Nominating => poll.voting_starts_at,
+                _ => None,
+            },
+            Schedule::Deadlines => match poll.phase {
+                PollPhase::Voting => poll.closes_at,
+                _ => None,
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl<T> PollStore for T
+where
+    T: DocumentStore,
+{
+    async fn add_poll(&self, ttl: usize, poll: Poll) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.insert(ttl, poll.clone())?;
+            Ok(poll)
+        })
+        .await
+    }
+
+    async fn get_poll(&self, poll_id: String) -> Result<Poll, Error> {
+        run(self, move |store| store.get(&poll_id)).await
+    }
+
+    async fn add_participant(
+        &self,
+        poll_id: String,
+        user_id: String,
+        name: String,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Join)?;
+                if poll.banned.contains(&user_id) {
+                    return Err(Error::Banned);
+                }
+                poll.participants.insert(user_id, name);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn remove_participant(&self, poll_id: String, user_id: String) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Leave)?;
+                poll.participants.remove(&user_id);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn ban_participant(&self, poll_id: String, user_id: String) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Ban)?;
+                poll.participants.remove(&user_id);
+                poll.rankings.remove(&user_id);
+                poll.approvals.remove(&user_id);
+                poll.scores.remove(&user_id);
+                poll.allocations.remove(&user_id);
+                poll.estimation.cards.remove(&user_id);
+                poll.banned.insert(user_id);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn transfer_admin(&self, poll_id: String, user_id: String) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::SetRole)?;
+                if !poll.participants.contains_key(&user_id) {
+                    return Err(Error::UnknownParticipant);
+                }
+                // already the admin, who must not become a moderator too
+                if poll.admin_id == user_id {
+                    return Ok(());
+                }
+                poll.moderators.remove(&user_id);
+                let admin_id = std::mem::replace(&mut poll.admin_id, user_id);
+                poll.moderators.insert(admin_id);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn set_moderator(
+        &self,
+        poll_id: String,
+        user_id: String,
+        moderator: bool,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::SetRole)?;
+                if !poll.participants.contains_key(&user_id) {
+                    return Err(Error::UnknownParticipant);
+                }
+                if moderator {
+                    poll.moderators.insert(user_id);
+                } else {
+                    poll.moderators.remove(&user_id);
+                }
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn add_invite(
+        &self,
+        poll_id: String,
+        invite_id: InviteID,
+        invite: Invite,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Invite)?;
+                poll.invites.insert(invite_id, invite);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn redeem_invite(&self, poll_id: String, invite_id: InviteID) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Invite)?;
+                let invite = poll
+                    .invites
+                    .get_mut(&invite_id)
+                    .ok_or(Error::InviteNotFound)?;
+                if invite.revoked {
+                    return Err(Error::InviteRevoked);
+                }
+                if invite.uses >= invite.max_uses {
+                    return Err(Error::InviteExhausted);
+                }
+                invite.uses += 1;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn revoke_invite(&self, poll_id: String, invite_id: InviteID) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Invite)?;
+                let invite = poll
+                    .invites
+                    .get_mut(&invite_id)
+                    .ok_or(Error::InviteNotFound)?;
+                invite.revoked = true;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn add_nomination(
+        &self,
+        poll_id: String,
+        nomination_id: NominationID,
+        nomination: Nomination,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Nominate)?;
+                poll.nominations.insert(nomination_id, nomination);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn remove_nomination(
+        &self,
+        poll_id: String,
+        nomination_id: NominationID,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::RemoveNomination)?;
+                poll.nominations.remove(&nomination_id);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn open_nominations(&self, poll_id: String) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::OpenNominations)
+            })
+        })
+        .await
+    }
+
+    async fn start_poll(&self, poll_id: String, closes_at: Option<i64>) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::StartVoting)?;
+                poll.closes_at = closes_at;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn clear_deadline(&self, poll_id: String) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::ClearDeadline)?;
+                poll.closes_at = None;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn add_participant_rankings(
+        &self,
+        poll_id: String,
+        user_id: String,
+        rankings: RankingList,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                accepts_ballot(poll, BallotType::Ranked)?;
+                poll.rankings.insert(user_id, rankings);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn add_participant_approvals(
+        &self,
+        poll_id: String,
+        user_id: String,
+        approvals: ApprovalList,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                accepts_ballot(poll, BallotType::Approval)?;
+                poll.approvals.insert(user_id, approvals);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn add_participant_scores(
+        &self,
+        poll_id: String,
+        user_id: String,
+        scores: ScoreList,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                accepts_ballot(poll, BallotType::Score)?;
+                poll.scores.insert(user_id, scores);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn add_participant_allocations(
+        &self,
+        poll_id: String,
+        user_id: String,
+        allocations: AllocationList,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                accepts_ballot(poll, BallotType::Cumulative)?;
+                poll.allocations.insert(user_id, allocations);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn start_estimate(&self, poll_id: String, item: NominationID) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                accepts_estimate(poll, false)?;
+                poll.estimation = Estimation {
+                    item: Some(item),
+                    ..Default::default()
+                };
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn add_card(
+        &self,
+        poll_id: String,
+        user_id: String,
+        card: String,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                accepts_estimate(poll, true)?;
+                poll.estimation.cards.insert(user_id, card);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn reveal_cards(&self, poll_id: String) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                accepts_estimate(poll, true)?;
+                poll.estimation.revealed = true;
+                if let Some(item) = poll.estimation.item.clone() {
+                    let summary = summarize(poll.deck, &poll.estimation.cards);
+                    poll.estimates.insert(item, summary);
+                }
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn add_results(
+        &self,
+        poll_id: String,
+        results: Results,
+        report: Option<TallyReport>,
+        no_quorum: bool,
+    ) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Close)?;
+                poll.results = results;
+                poll.report = report;
+                poll.no_quorum = no_quorum;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn set_runoff(&self, poll_id: String, runoff_id: String) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| {
+                poll.phase.apply(PollAction::Runoff)?;
+                if poll.runoff_id.is_some() {
+                    return Err(Error::RunoffExists);
+                }
+                poll.runoff_id = Some(runoff_id);
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn cancel_poll(&self, poll_id: String) -> Result<Poll, Error> {
+        run(self, move |store| {
+            store.update(&poll_id, |poll| poll.phase.apply(PollAction::Cancel))
+        })
+        .await
+    }
+
+    async fn del_poll(&self, poll_id: String) -> Result<(), Error> {
+        run(self, move |store| store.remove(&poll_id)).await
+    }
+
+    async fn voting_starts(&self) -> Result<Vec<(String, i64)>, Error> {
+        run(self, |store| store.scheduled(Schedule::VotingStarts)).await
+    }
+
+    async fn deadlines(&self) -> Result<Vec<(String, i64)>, Error> {
+        run(self, |store| store.scheduled(Schedule::Deadlines)).await
+    }
+
+    async fn watch_removed(&self, f: Box<dyn Fn(String) + Send + Sync>) -> anyhow::Result<()> {
+        let mut removed_rx = self.removed_tx().subscribe();
+        let mut sweep = tokio::time::interval(SWEEP_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = sweep.tick() => run(self, |store| store.remove_expired()).await?,
+                Ok(poll_id) = removed_rx.recv() => f(poll_id),
+            }
+        }
+    }
+}
+
+/// Runs `f` against the store, moving it to the blocking pool if the store's
+/// calls block.
+async fn run<T, R, F>(store: &T, f: F) -> Result<R, Error>
+where
+    T: DocumentStore,
+    R: Send + 'static,
+    F: FnOnce(&T) -> Result<R, Error> + Send + 'static,
+{
+    if !T::BLOCKING {
+        return f(store);
+    }
+    let store = store.clone();
+    match tokio::task::spawn_blocking(move || f(&store)).await {
+        Ok(result) => result,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+fn accepts_ballot(poll: &mut Poll, ballot_type: BallotType) -> Result<(), Error> {
+    poll.phase.apply(PollAction::Vote)?;
+    if poll.kind != PollKind::Ranking {
+        return Err(Error::WrongPollKind);
+    }
+    if poll.ballot_type != ballot_type {
+        return Err(Error::WrongBallotType);
+    }
+    Ok(())
+}
+
+fn accepts_estimate(poll: &mut Poll, needs_open_round: bool) -> Result<(), Error> {
+    poll.phase.apply(PollAction::Vote)?;
+    if poll.kind != PollKind::Estimation {
+        return Err(Error::WrongPollKind);
+    }
+    if needs_open_round {
+        if poll.estimation.item.is_none() {
+            return Err(Error::NoEstimateItem);
+        }
+        if poll.estimation.revealed {
+            return Err(Error::CardsRevealed);
+        }
+    }
+    Ok(())
+}
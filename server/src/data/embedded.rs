@@ -0,0 +1,361 @@
+use std::{path::Path, sync::Arc};
+
+use redb::{Database, ReadableTable, TableDefinition, WriteTransaction};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::{
+    data::{DocumentStore, Schedule},
+    errors::Error,
+    models::Poll,
+};
+
+/// Poll id -> json encoded `Entry`.
+const POLLS: TableDefinition<&str, &str> = TableDefinition::new("polls");
+
+/// Poll id -> due time, for `Schedule::VotingStarts`.
+const VOTING_STARTS: TableDefinition<&str, i64> = TableDefinition::new("voting_starts");
+
+/// Poll id -> due time, for `Schedule::Deadlines`.
+const DEADLINES: TableDefinition<&str, i64> = TableDefinition::new("deadlines");
+
+/// Poll id -> `Entry.expire_at`, so expiry checks don't decode whole polls.
+const EXPIRIES: TableDefinition<&str, i64> = TableDefinition::new("expiries");
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    poll: Poll,
+    /// Unix timestamp in milliseconds.
+    expire_at: i64,
+}
+
+impl Entry {
+    fn is_expired(&self) -> bool {
+        is_expired(self.expire_at)
+    }
+}
+
+impl TryFrom<&str> for Entry {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        serde_json::from_str(value).map_err(Error::DeserializeJsonError)
+    }
+}
+
+/// File backed `PollStore` on top of redb, for single node deployments.
+///
+/// Polls survive a restart and still expire after their ttl.
+#[derive(Clone)]
+pub struct EmbeddedPollStore {
+    db: Arc<Database>,
+    removed_tx: broadcast::Sender<String>,
+}
+
+impl EmbeddedPollStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let db = Database::create(path).map_err(embedded_error)?;
+
+        // make sure the tables exist before the first read, and rebuild the
+        // indexes so files written before they existed are indexed
+        let txn = db.begin_write().map_err(embedded_error)?;
+        let mut entries = Vec::new();
+        {
+            let table = txn.open_table(POLLS).map_err(embedded_error)?;
+            for item in table.iter().map_err(embedded_error)? {
+                let (_, value) = item.map_err(embedded_error)?;
+                entries.push(Entry::try_from(value.value())?);
+            }
+        }
+        for schedule in Schedule::ALL {
+            txn.delete_table(index(schedule)).map_err(embedded_error)?;
+        }
+        txn.delete_table(EXPIRIES).map_err(embedded_error)?;
+        {
+            let mut expiries = txn.open_table(EXPIRIES).map_err(embedded_error)?;
+            for entry in entries.iter() {
+                expiries
+                    .insert(entry.poll.id.as_str(), entry.expire_at)
+                    .map_err(embedded_error)?;
+            }
+        }
+        for entry in entries.iter() {
+            reindex(&txn, &entry.poll)?;
+        }
+        txn.commit().map_err(embedded_error)?;
+
+        let (removed_tx, _rx) = broadcast::channel(100);
+        Ok(Self {
+            db: Arc::new(db),
+            removed_tx,
+        })
+    }
+}
+
+impl DocumentStore for EmbeddedPollStore {
+    const BLOCKING: bool = true;
+
+    fn insert(&self, ttl: usize, poll: Poll) -> Result<(), Error> {
+        let entry = Entry {
+            expire_at: chrono::Utc::now().timestamp_millis() + ttl as i64 * 1000,
+            poll,
+        };
+        let value = serde_json::to_string(&entry)?;
+
+        let txn = self.db.begin_write().map_err(embedded_error)?;
+        {
+            let mut table = txn.open_table(POLLS).map_err(embedded_error)?;
+            table
+                .insert(entry.poll.id.as_str(), value.as_str())
+                .map_err(embedded_error)?;
+            let mut expiries = txn.open_table(EXPIRIES).map_err(embedded_error)?;
+            expiries
+                .insert(entry.poll.id.as_str(), entry.expire_at)
+                .map_err(embedded_error)?;
+        }
+        reindex(&txn, &entry.poll)?;
+        txn.commit().map_err(embedded_error)
+    }
+
+    fn get(&self, poll_id: &str) -> Result<Poll, Error> {
+        let txn = self.db.begin_read().map_err(embedded_error)?;
+        let table = txn.open_table(POLLS).map_err(embedded_error)?;
+        let Some(value) = table.get(poll_id).map_err(embedded_error)? else {
+            return Err(Error::PollNotFound);
+        };
+        let entry = Entry::try_from(value.value())?;
+        if entry.is_expired() {
+            return Err(Error::PollNotFound);
+        }
+        Ok(entry.poll)
+    }
+
+    fn update<F>(&self, poll_id: &str, f: F) -> Result<Poll, Error>
+    where
+        F: FnOnce(&mut Poll) -> Result<(), Error>,
+    {
+        let txn = self.db.begin_write().map_err(embedded_error)?;
+        let mut entry = {
+            let table = txn.open_table(POLLS).map_err(embedded_error)?;
+            let Some(value) = table.get(poll_id).map_err(embedded_error)? else {
+                return Err(Error::PollNotFound);
+            };
+            Entry::try_from(value.value())?
+        };
+        if entry.is_expired() {
+            // expired but not swept yet, the transaction is dropped
+            return Err(Error::PollNotFound);
+        }
+
+        f(&mut entry.poll)?;
+        let value = serde_json::to_string(&entry)?;
+        {
+            let mut table = txn.open_table(POLLS).map_err(embedded_error)?;
+            table
+                .insert(poll_id, value.as_str())
+                .map_err(embedded_error)?;
+        }
+        reindex(&txn, &entry.poll)?;
+        txn.commit().map_err(embedded_error)?;
+
+        Ok(entry.poll)
+    }
+
+    fn remove(&self, poll_id: &str) -> Result<(), Error> {
+        let txn = self.db.begin_write().map_err(embedded_error)?;
+        let removed = {
+            let mut table = txn.open_table(POLLS).map_err(embedded_error)?;
+            let removed = table.remove(poll_id).map_err(embedded_error)?;
+            removed.is_some()
+        };
+        unindex(&txn, poll_id)?;
+        txn.commit().map_err(embedded_error)?;
+
+        if removed {
+            let _ = self.removed_tx.send(poll_id.to_string());
+        }
+        Ok(())
+    }
+
+    fn scheduled(&self, schedule: Schedule) -> Result<Vec<(String, i64)>, Error> {
+        let txn = self.db.begin_read().map_err(embedded_error)?;
+        let table = txn.open_table(index(schedule)).map_err(embedded_error)?;
+        let expiries = txn.open_table(EXPIRIES).map_err(embedded_error)?;
+        let mut scheduled = Vec::new();
+        for item in table.iter().map_err(embedded_error)? {
+            let (key, value) = item.map_err(embedded_error)?;
+            let Some(expire_at) = expiries.get(key.value()).map_err(embedded_error)? else {
+                continue;
+            };
+            if !is_expired(expire_at.value()) {
+                scheduled.push((key.value().to_string(), value.value()));
+            }
+        }
+        Ok(scheduled)
+    }
+
+    fn remove_expired(&self) -> Result<(), Error> {
+        let mut expired = Vec::new();
+        {
+            let txn = self.db.begin_read().map_err(embedded_error)?;
+            let table = txn.open_table(EXPIRIES).map_err(embedded_error)?;
+            for item in table.iter().map_err(embedded_error)? {
+                let (key, value) = item.map_err(embedded_error)?;
+                if is_expired(value.value()) {
+                    expired.push(key.value().to_string());
+                }
+            }
+        }
+        // only write, and sync to disk, when there is something to remove
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let txn = self.db.begin_write().map_err(embedded_error)?;
+        {
+            let mut table = txn.open_table(POLLS).map_err(embedded_error)?;
+            for poll_id in expired.iter() {
+                table.remove(poll_id.as_str()).map_err(embedded_error)?;
+            }
+        }
+        for poll_id in expired.iter() {
+            unindex(&txn, poll_id)?;
+        }
+        txn.commit().map_err(embedded_error)?;
+
+        for poll_id in expired {
+            let _ = self.removed_tx.send(poll_id);
+        }
+        Ok(())
+    }
+
+    fn removed_tx(&self) -> &broadcast::Sender<String> {
+        &self.removed_tx
+    }
+}
+
+fn is_expired(expire_at: i64) -> bool {
+    expire_at <= chrono::Utc::now().timestamp_millis()
+}
+
+fn embedded_error<E: Into<redb::Error>>(err: E) -> Error {
+    Error::EmbeddedError(Box::new(err.into()))
+}
+
+fn index(schedule: Schedule) -> TableDefinition<'static, &'static str, i64> {
+    match schedule {
+        Schedule::VotingStarts => VOTING_STARTS,
+        Schedule::Deadlines => DEADLINES,
+    }
+}
+
+/// Keeps the schedule indexes in step with `poll`, in the transaction that
+/// writes it.
+fn reindex(txn: &WriteTransaction, poll: &Poll) -> Result<(), Error> {
+    for schedule in Schedule::ALL {
+        let mut table = txn.open_table(index(schedule)).map_err(embedded_error)?;
+        match schedule.due_at(poll) {
+            Some(at) => table.insert(poll.id.as_str(), at).map(|_| ()),
+            None => table.remove(poll.id.as_str()).map(|_| ()),
+        }
+        .map_err(embedded_error)?;
+    }
+    Ok(())
+}
+
+/// Drops `poll_id` from every index, in the transaction that removes it.
+fn unindex(txn: &WriteTransaction, poll_id: &str) -> Result<(), Error> {
+    for schedule in Schedule::ALL {
+        let mut table = txn.open_table(index(schedule)).map_err(embedded_error)?;
+        table.remove(poll_id).map_err(embedded_error)?;
+    }
+    let mut expiries = txn.open_table(EXPIRIES).map_err(embedded_error)?;
+    expiries.remove(poll_id).map_err(embedded_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::PollStore,
+        tests::fixtures::{nominating_poll, poll, POLL_ID},
+    };
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ranker-{}.redb", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_polls_should_survive_reopen() {
+        let path = temp_path();
+        let poll_id = POLL_ID.to_string();
+        {
+            let store = EmbeddedPollStore::open(&path).unwrap();
+            nominating_poll(&store, poll(), &["u2"]).await;
+        }
+
+        let store = EmbeddedPollStore::open(&path).unwrap();
+        let poll = store.get_poll(poll_id).await.unwrap();
+        assert_eq!(Some(&"u2".to_string()), poll.participants.get("u2"));
+        assert!(poll.nominations.contains_key("n1"));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired_should_notify() {
+        let path = temp_path();
+        let store = EmbeddedPollStore::open(&path).unwrap();
+        let mut removed_rx = store.removed_tx().subscribe();
+        store.add_poll(0, poll()).await.unwrap();
+
+        store.remove_expired().unwrap();
+        assert_eq!(POLL_ID.to_string(), removed_rx.recv().await.unwrap());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_expiries_should_survive_reopen() {
+        let path = temp_path();
+        {
+            let store = EmbeddedPollStore::open(&path).unwrap();
+            store.add_poll(0, poll()).await.unwrap();
+        }
+
+        let store = EmbeddedPollStore::open(&path).unwrap();
+        let mut removed_rx = store.removed_tx().subscribe();
+        store.remove_expired().unwrap();
+        assert_eq!(POLL_ID.to_string(), removed_rx.recv().await.unwrap());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_deadlines_should_survive_reopen() {
+        let path = temp_path();
+        let poll_id = POLL_ID.to_string();
+        {
+            let store = EmbeddedPollStore::open(&path).unwrap();
+            nominating_poll(&store, poll(), &[]).await;
+            assert!(store.deadlines().await.unwrap().is_empty());
+
+            store.start_poll(poll_id.clone(), Some(42)).await.unwrap();
+        }
+
+        let store = EmbeddedPollStore::open(&path).unwrap();
+        assert_eq!(
+            vec![(poll_id.clone(), 42)],
+            store.deadlines().await.unwrap()
+        );
+
+        store
+            .add_results(poll_id, vec![], None, false)
+            .await
+            .unwrap();
+        assert!(store.deadlines().await.unwrap().is_empty());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
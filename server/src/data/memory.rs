@@ -0,0 +1,415 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use tokio::sync::broadcast;
+
+use crate::{
+    data::{DocumentStore, Schedule},
+    errors::Error,
+    models::Poll,
+};
+
+#[derive(Debug)]
+struct Entry {
+    poll: Poll,
+    expire_at: Instant,
+}
+
+impl Entry {
+    fn is_expired(&self) -> bool {
+        self.expire_at <= Instant::now()
+    }
+}
+
+/// In-process `PollStore`, for running the server without Redis.
+///
+/// Polls expire after their ttl like Redis keys do, and nothing survives a
+/// restart.
+#[derive(Debug, Clone)]
+pub struct MemoryPollStore {
+    polls: Arc<DashMap<String, Entry>>,
+    /// Poll id -> due time, one index per `Schedule`. Written while `polls`
+    /// is locked, so never read while holding it.
+    voting_starts: Arc<DashMap<String, i64>>,
+    deadlines: Arc<DashMap<String, i64>>,
+    removed_tx: broadcast::Sender<String>,
+}
+
+impl MemoryPollStore {
+    pub fn new() -> Self {
+        let (removed_tx, _rx) = broadcast::channel(100);
+        Self {
+            polls: Arc::new(DashMap::new()),
+            voting_starts: Arc::new(DashMap::new()),
+            deadlines: Arc::new(DashMap::new()),
+            removed_tx,
+        }
+    }
+
+    fn index(&self, schedule: Schedule) -> &DashMap<String, i64> {
+        match schedule {
+            Schedule::VotingStarts => &self.voting_starts,
+            Schedule::Deadlines => &self.deadlines,
+        }
+    }
+
+    fn reindex(&self, poll: &Poll) {
+        for schedule in Schedule::ALL {
+            match schedule.due_at(poll) {
+                Some(at) => {
+                    self.index(schedule).insert(poll.id.clone(), at);
+                }
+                None => {
+                    self.index(schedule).remove(&poll.id);
+                }
+            }
+        }
+    }
+}
+
+impl Default for MemoryPollStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DocumentStore for MemoryPollStore {
+    fn insert(&self, ttl: usize, poll: Poll) -> Result<(), Error> {
+        let entry = Entry {
+            expire_at: Instant::now() + Duration::from_secs(ttl as u64),
+            poll,
+        };
+        self.reindex(&entry.poll);
+        self.polls.insert(entry.poll.id.clone(), entry);
+        Ok(())
+    }
+
+    fn get(&self, poll_id: &str) -> Result<Poll, Error> {
+        match self.polls.get(poll_id) {
+            Some(entry) if !entry.is_expired() => Ok(entry.poll.clone()),
+            _ => Err(Error::PollNotFound),
+        }
+    }
+
+    fn update<F>(&self, poll_id: &str, f: F) -> Result<Poll, Error>
+    where
+        F: FnOnce(&mut Poll) -> Result<(), Error>,
+    {
+        {
+            let Some(mut entry) = self.polls.get_mut(poll_id) else {
+                return Err(Error::PollNotFound);
+            };
+            if !entry.is_expired() {
+                let mut poll = entry.poll.clone();
+                f(&mut poll)?;
+                self.reindex(&poll);
+                entry.poll = poll.clone();
+                return Ok(poll);
+            }
+        }
+
+        // expired but not swept yet
+        self.remove(poll_id)?;
+        Err(Error::PollNotFound)
+    }
+
+    fn remove(&self, poll_id: &str) -> Result<(), Error> {
+        for schedule in Schedule::ALL {
+            self.index(schedule).remove(poll_id);
+        }
+        if self.polls.remove(poll_id).is_some() {
+            let _ = self.removed_tx.send(poll_id.to_string());
+        }
+        Ok(())
+    }
+
+    fn scheduled(&self, schedule: Schedule) -> Result<Vec<(String, i64)>, Error> {
+        // copied out first, so the index is not locked while polls are read
+        let entries: Vec<(String, i64)> = self
+            .index(schedule)
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        let scheduled = entries
+            .into_iter()
+            .filter(|(poll_id, _)| {
+                self.polls
+                    .get(poll_id)
+                    .is_some_and(|entry| !entry.is_expired())
+            })
+            .collect();
+        Ok(scheduled)
+    }
+
+    fn remove_expired(&self) -> Result<(), Error> {
+        let expired: Vec<String> = self
+            .polls
+            .iter()
+            .filter(|entry| entry.is_expired())
+            .map(|entry| entry.key().clone())
+            .collect();
+        for poll_id in expired {
+            self.remove(&poll_id)?;
+        }
+        Ok(())
+    }
+
+    fn removed_tx(&self) -> &broadcast::Sender<String> {
+        &self.removed_tx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{
+        data::PollStore,
+        models::{BallotType, Invite, PollKind, Role},
+        tests::fixtures::{nominating_poll, poll, voting_poll, POLL_ID},
+    };
+
+    #[tokio::test]
+    async fn test_update_should_not_found() {
+        let store = MemoryPollStore::new();
+        let Err(err) = store.get_poll("unknown".to_string()).await else {
+            panic!("should be error but not error")
+        };
+        assert_eq!(err, Error::PollNotFound)
+    }
+
+    #[tokio::test]
+    async fn test_update_should_not_write_on_error() {
+        let store = MemoryPollStore::new();
+        let poll_id = POLL_ID.to_string();
+        store.add_poll(60, poll()).await.unwrap();
+
+        let Err(err) = store
+            .add_participant_rankings(poll_id.clone(), "u1".to_string(), vec!["n1".to_string()])
+            .await
+        else {
+            panic!("should be error but not error")
+        };
+        assert_eq!(err, Error::InvalidPhase);
+
+        let poll = store.get_poll(poll_id).await.unwrap();
+        assert!(poll.rankings.is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_add_ballot_should_match_ballot_type() {
+        let store = MemoryPollStore::new();
+        let poll_id = POLL_ID.to_string();
+        let poll = Poll {
+            ballot_type: BallotType::Approval,
+            ..poll()
+        };
+        voting_poll(&store, poll, &[]).await;
+
+        let Err(err) = store
+            .add_participant_rankings(poll_id.clone(), "u1".to_string(), vec!["n1".to_string()])
+            .await
+        else {
+            panic!("should be error but not error")
+        };
+        assert_eq!(err, Error::WrongBallotType);
+
+        let poll = store
+            .add_participant_approvals(poll_id, "u1".to_string(), vec!["n1".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(1, poll.approvals.len())
+    }
+
+    #[tokio::test]
+    async fn test_add_card_should_need_open_round() {
+        let store = MemoryPollStore::new();
+        let poll_id = POLL_ID.to_string();
+        let poll = Poll {
+            kind: PollKind::Estimation,
+            ..poll()
+        };
+        voting_poll(&store, poll, &[]).await;
+
+        let Err(err) = store
+            .add_card(poll_id.clone(), "u1".to_string(), "3".to_string())
+            .await
+        else {
+            panic!("should be error but not error")
+        };
+        assert_eq!(err, Error::NoEstimateItem);
+
+        store
+            .start_estimate(poll_id.clone(), "n1".to_string())
+            .await
+            .unwrap();
+        store
+            .add_card(poll_id.clone(), "u1".to_string(), "3".to_string())
+            .await
+            .unwrap();
+        let poll = store.reveal_cards(poll_id.clone()).await.unwrap();
+        assert_eq!(Some(&"3".to_string()), poll.estimation.cards.get("u1"));
+        assert_eq!(Some("3".to_string()), poll.estimates["n1"].estimate);
+
+        let Err(err) = store
+            .add_card(poll_id.clone(), "u1".to_string(), "5".to_string())
+            .await
+        else {
+            panic!("should be error but not error")
+        };
+        assert_eq!(err, Error::CardsRevealed);
+
+        // a new round drops the cards
+        let poll = store
+            .start_estimate(poll_id, "n2".to_string())
+            .await
+            .unwrap();
+        assert!(poll.estimation.cards.is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_add_participant_should_refuse_banned() {
+        let store = MemoryPollStore::new();
+        let poll_id = POLL_ID.to_string();
+        store.add_poll(60, poll()).await.unwrap();
+        store
+            .add_participant(poll_id.clone(), "u2".to_string(), "ben".to_string())
+            .await
+            .unwrap();
+
+        let poll = store
+            .ban_participant(poll_id.clone(), "u2".to_string())
+            .await
+            .unwrap();
+        assert!(poll.participants.is_empty());
+
+        let Err(err) = store
+            .add_participant(poll_id, "u2".to_string(), "ben".to_string())
+            .await
+        else {
+            panic!("should be error but not error")
+        };
+        assert_eq!(err, Error::Banned);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_admin_should_keep_previous_as_moderator() {
+        let store = MemoryPollStore::new();
+        let poll_id = POLL_ID.to_string();
+        store.add_poll(60, poll()).await.unwrap();
+
+        let Err(err) = store
+            .transfer_admin(poll_id.clone(), "u2".to_string())
+            .await
+        else {
+            panic!("should be error but not error")
+        };
+        assert_eq!(err, Error::UnknownParticipant);
+
+        store
+            .add_participant(poll_id.clone(), "u2".to_string(), "ben".to_string())
+            .await
+            .unwrap();
+        let poll = store
+            .transfer_admin(poll_id.clone(), "u2".to_string())
+            .await
+            .unwrap();
+        assert_eq!(Role::Admin, poll.role("u2"));
+        assert_eq!(Role::Moderator, poll.role("u1"));
+
+        // handing it to the admin changes nothing
+        let poll = store
+            .transfer_admin(poll_id, "u2".to_string())
+            .await
+            .unwrap();
+        assert_eq!("u2", poll.admin_id);
+        assert_eq!(BTreeSet::from(["u1".to_string()]), poll.moderators);
+    }
+
+    #[tokio::test]
+    async fn test_voting_starts_should_drop_started_polls() {
+        let store = MemoryPollStore::new();
+        let poll_id = POLL_ID.to_string();
+        let poll = Poll {
+            voting_starts_at: Some(42),
+            ..poll()
+        };
+        nominating_poll(&store, poll, &[]).await;
+        assert_eq!(
+            vec![(poll_id.clone(), 42)],
+            store.voting_starts().await.unwrap()
+        );
+
+        store.start_poll(poll_id, None).await.unwrap();
+        assert!(store.voting_starts().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watch_removed_should_notify_expired() {
+        let store = MemoryPollStore::new();
+        let poll_id = POLL_ID.to_string();
+        store.add_poll(0, poll()).await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = store.clone();
+        tokio::spawn(async move {
+            watcher
+                .watch_removed(Box::new(move |poll_id| {
+                    let _ = tx.send(poll_id);
+                }))
+                .await
+        });
+
+        assert_eq!(Some(poll_id), rx.recv().await);
+    }
+
+    #[tokio::test]
+    async fn test_redeem_invite_should_count_uses() {
+        let store = MemoryPollStore::new();
+        let poll_id = POLL_ID.to_string();
+        store.add_poll(60, poll()).await.unwrap();
+        let invite = Invite {
+            max_uses: 2,
+            ..Default::default()
+        };
+        store
+            .add_invite(poll_id.clone(), "i1".to_string(), invite.clone())
+            .await
+            .unwrap();
+        store
+            .add_invite(poll_id.clone(), "i2".to_string(), invite)
+            .await
+            .unwrap();
+
+        store
+            .redeem_invite(poll_id.clone(), "i1".to_string())
+            .await
+            .unwrap();
+        let poll = store
+            .redeem_invite(poll_id.clone(), "i1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(2, poll.invites["i1"].uses);
+        assert_eq!(
+            Err(Error::InviteExhausted),
+            store.redeem_invite(poll_id.clone(), "i1".to_string()).await
+        );
+
+        store
+            .revoke_invite(poll_id.clone(), "i2".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            Err(Error::InviteRevoked),
+            store.redeem_invite(poll_id.clone(), "i2".to_string()).await
+        );
+        assert_eq!(
+            Err(Error::InviteNotFound),
+            store.redeem_invite(poll_id, "i3".to_string()).await
+        );
+    }
+}
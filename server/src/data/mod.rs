@@ -1 +1,8 @@
+pub mod embedded;
+pub mod memory;
 pub mod redis;
+
+mod document;
+mod store;
+pub use document::*;
+pub use store::*;
@@ -1,6 +1,8 @@
 use redis::{cmd, Client};
 
 pub mod polls;
+mod store;
+pub use store::*;
 
 // Redis keyspace notifications: https://redis.io/docs/manual/keyspace-notifications/
 // github.com/Nurrl/shrekd/blob/main/src/main.rs#L79
@@ -19,7 +21,7 @@ where
         .arg("SET")
         .arg("notify-keyspace-events")
         .arg("Egx") /* `Egx` means E: keyevent events, with types g: general and x: expired */
-        .query_async(&mut conn)
+        .query_async::<_, ()>(&mut conn)
         .await?;
 
     /* Subscribe to the relevant events */
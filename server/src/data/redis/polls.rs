@@ -2,26 +2,30 @@ use redis::{aio::ConnectionLike, cmd};
 
 use crate::{
     errors::Error,
-    models::{Nomination, NominationID, Poll, RankingList, Results},
+    models::{
+        AllocationList, ApprovalList, BallotType, Estimation, Invite, InviteID, Nomination,
+        NominationID, Poll, PollAction, PollKind, RankingList, Results, ScoreList,
+    },
+    scoring::{summarize, TallyReport},
 };
 
 pub const POLL_KEY_PREFIX: &str = "polls:";
 
-pub async fn add_poll<C>(
-    con: &mut C,
-    ttl: usize,
-    poll_id: String,
-    topic: String,
-    votes_per_voter: usize,
-    user_id: String,
-) -> Result<Poll, Error>
+/// Sorted set of the ids of polls with a deadline, scored by `Poll.closes_at`.
+pub const DEADLINES_KEY: &str = "deadlines";
+
+/// Sorted set of the ids of polls waiting for voting to start, scored by
+/// `Poll.voting_starts_at`.
+pub const VOTING_STARTS_KEY: &str = "voting_starts";
+
+pub async fn add_poll<C>(con: &mut C, ttl: usize, poll: Poll) -> Result<Poll, Error>
 where
     C: ConnectionLike,
 {
-    let key = make_key(poll_id.clone());
+    let key = make_key(poll.id.clone());
     let path = ".".to_string();
-    let poll = Poll::new(poll_id, topic, votes_per_voter, user_id);
     let value = poll.string();
+    let voting_starts_at = serde_json::to_string(&poll.voting_starts_at).unwrap();
     redis::Script::new(
         r#"
         local key = KEYS[1]
@@ -30,6 +34,9 @@ where
         local ttl = ARGV[3]
         redis.call('JSON.SET', key, path, value)
         redis.call('EXPIRE', key, ttl)
+        if ARGV[4] ~= 'null' then
+            redis.call('ZADD', ARGV[5], ARGV[4], ARGV[6])
+        end
         return 0
     "#,
     )
@@ -37,7 +44,10 @@ where
     .arg(path)
     .arg(value)
     .arg(ttl.to_string())
-    .invoke_async(con)
+    .arg(voting_starts_at)
+    .arg(VOTING_STARTS_KEY)
+    .arg(&poll.id)
+    .invoke_async::<_, ()>(con)
     .await
     .map_err(Error::RedisError)?;
 
@@ -85,9 +95,23 @@ where
     C: ConnectionLike,
 {
     let key = make_key(poll_id);
+    let banned = serde_json::to_string(&user_id).unwrap();
     let path = make_participant_path(user_id);
     let value = serde_json::to_string(&name).unwrap();
-    set_path_value(con, key, path, value).await
+
+    update_poll(
+        con,
+        key,
+        PollAction::Join,
+        r#"
+        if redis.call('JSON.ARRINDEX', key, '.banned', ARGV[5]) ~= -1 then
+            return '-8'
+        end
+        redis.call('JSON.SET', key, ARGV[3], ARGV[4])
+    "#,
+        &[path, value, banned],
+    )
+    .await
 }
 
 pub async fn remove_participant<C>(
@@ -100,40 +124,183 @@ where
 {
     let key = make_key(poll_id);
     let path = make_participant_path(user_id);
-    // can remove if no has_started == 'false'
-    let poll_json: String = redis::Script::new(
+    remove_path_value(con, key, path, PollAction::Leave).await
+}
+
+pub async fn ban_participant<C>(
+    con: &mut C,
+    poll_id: String,
+    user_id: String,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let banned = serde_json::to_string(&user_id).unwrap();
+    let paths = [
+        make_participant_path(user_id.clone()),
+        make_rankings_path(user_id.clone()),
+        make_approvals_path(user_id.clone()),
+        make_scores_path(user_id.clone()),
+        make_allocations_path(user_id.clone()),
+        make_card_path(user_id),
+    ];
+    let paths = serde_json::to_string(&paths).unwrap();
+
+    update_poll(
+        con,
+        key,
+        PollAction::Ban,
         r#"
-        local key = KEYS[1]
-        local path = ARGV[1]
-        local value = ARGV[2]
-        if redis.call('EXISTS', key) == 1 then  
-            if redis.call('JSON.GET', key, '.has_started') == 'false' then
-                redis.call('JSON.DEL', key, path)
-                return redis.call('JSON.GET', key, '.') 
-            else
-                return '-2'
-            end
-        else
-            return '-1'
+        for _, path in ipairs(cjson.decode(ARGV[3])) do
+            redis.call('JSON.DEL', key, path)
+        end
+        if redis.call('JSON.ARRINDEX', key, '.banned', ARGV[4]) == -1 then
+            redis.call('JSON.ARRAPPEND', key, '.banned', ARGV[4])
         end
     "#,
+        &[paths, banned],
     )
-    .key(key)
-    .arg(path)
-    .invoke_async(con)
     .await
-    .map_err(Error::RedisError)?;
+}
 
-    if poll_json == "-1" {
-        return Err(Error::PollNotFound);
-    }
+pub async fn transfer_admin<C>(con: &mut C, poll_id: String, user_id: String) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let admin_id = serde_json::to_string(&user_id).unwrap();
 
-    if poll_json == "-2" {
-        return Err(Error::PollHasStarted);
-    }
+    update_poll(
+        con,
+        key,
+        PollAction::SetRole,
+        &format!(
+            r#"
+        {CHECK_PARTICIPANT}
+        local admin_id = redis.call('JSON.GET', key, '.admin_id')
+        if admin_id ~= ARGV[4] then
+            local moderator = redis.call('JSON.ARRINDEX', key, '.moderators', ARGV[4])
+            if moderator ~= -1 then
+                redis.call('JSON.ARRPOP', key, '.moderators', moderator)
+            end
+            if redis.call('JSON.ARRINDEX', key, '.moderators', admin_id) == -1 then
+                redis.call('JSON.ARRAPPEND', key, '.moderators', admin_id)
+            end
+            redis.call('JSON.SET', key, '.admin_id', ARGV[4])
+        end
+    "#
+        ),
+        &[user_id, admin_id],
+    )
+    .await
+}
 
-    let poll: Poll = poll_json.try_into()?;
-    Ok(poll)
+pub async fn set_moderator<C>(
+    con: &mut C,
+    poll_id: String,
+    user_id: String,
+    moderator: bool,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let moderator_id = serde_json::to_string(&user_id).unwrap();
+
+    update_poll(
+        con,
+        key,
+        PollAction::SetRole,
+        &format!(
+            r#"
+        {CHECK_PARTICIPANT}
+        local moderator = redis.call('JSON.ARRINDEX', key, '.moderators', ARGV[4])
+        if ARGV[5] == 'true' then
+            if moderator == -1 then
+                redis.call('JSON.ARRAPPEND', key, '.moderators', ARGV[4])
+            end
+        elseif moderator ~= -1 then
+            redis.call('JSON.ARRPOP', key, '.moderators', moderator)
+        end
+    "#
+        ),
+        &[user_id, moderator_id, moderator.to_string()],
+    )
+    .await
+}
+
+pub async fn add_invite<C>(
+    con: &mut C,
+    poll_id: String,
+    invite_id: InviteID,
+    invite: Invite,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let path = make_invite_path(invite_id);
+    let value = serde_json::to_string(&invite).unwrap();
+    set_path_value(con, key, path, value, PollAction::Invite).await
+}
+
+pub async fn redeem_invite<C>(
+    con: &mut C,
+    poll_id: String,
+    invite_id: InviteID,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let path = make_invite_path(invite_id.clone());
+
+    update_poll(
+        con,
+        key,
+        PollAction::Invite,
+        &format!(
+            r#"
+        {CHECK_INVITE}
+        if invite.revoked then
+            return '-11'
+        end
+        if invite.uses >= invite.max_uses then
+            return '-12'
+        end
+        redis.call('JSON.NUMINCRBY', key, ARGV[4] .. '.uses', 1)
+    "#
+        ),
+        &[invite_id, path],
+    )
+    .await
+}
+
+pub async fn revoke_invite<C>(
+    con: &mut C,
+    poll_id: String,
+    invite_id: InviteID,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let path = make_invite_path(invite_id.clone());
+
+    update_poll(
+        con,
+        key,
+        PollAction::Invite,
+        &format!(
+            r#"
+        {CHECK_INVITE}
+        redis.call('JSON.SET', key, ARGV[4] .. '.revoked', 'true')
+    "#
+        ),
+        &[invite_id, path],
+    )
+    .await
 }
 
 pub async fn add_nomination<C>(
@@ -149,7 +316,7 @@ where
     let path = make_nomination_path(nomination_id);
     let value = serde_json::to_string(&nomination).unwrap();
 
-    set_path_value(con, key, path, value).await
+    set_path_value(con, key, path, value, PollAction::Nominate).await
 }
 
 pub async fn remove_nomination<C>(
@@ -163,18 +330,65 @@ where
     let key = make_key(poll_id);
     let path = make_nomination_path(nomination_id);
 
-    remove_path_value(con, key, path).await
+    remove_path_value(con, key, path, PollAction::RemoveNomination).await
 }
 
-pub async fn start_poll<C>(con: &mut C, poll_id: String) -> Result<Poll, Error>
+pub async fn open_nominations<C>(con: &mut C, poll_id: String) -> Result<Poll, Error>
 where
     C: ConnectionLike,
 {
     let key = make_key(poll_id);
-    let path = ".has_started".to_string();
-    let started = true;
-    let value = serde_json::to_string(&started).unwrap();
-    set_path_value(con, key, path, value).await
+    update_poll(con, key, PollAction::OpenNominations, "", &[]).await
+}
+
+pub async fn start_poll<C>(
+    con: &mut C,
+    poll_id: String,
+    closes_at: Option<i64>,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id.clone());
+    let closes_at = serde_json::to_string(&closes_at).unwrap();
+
+    update_poll(
+        con,
+        key,
+        PollAction::StartVoting,
+        r#"
+        if ARGV[3] ~= 'null' then
+            redis.call('JSON.SET', key, '.closes_at', ARGV[3])
+            redis.call('ZADD', ARGV[4], ARGV[3], ARGV[5])
+        end
+        redis.call('ZREM', ARGV[6], ARGV[5])
+    "#,
+        &[
+            closes_at,
+            DEADLINES_KEY.to_string(),
+            poll_id,
+            VOTING_STARTS_KEY.to_string(),
+        ],
+    )
+    .await
+}
+
+pub async fn clear_deadline<C>(con: &mut C, poll_id: String) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id.clone());
+    update_poll(
+        con,
+        key,
+        PollAction::ClearDeadline,
+        r#"
+        redis.call('JSON.SET', key, '.closes_at', 'null')
+        redis.call('ZREM', ARGV[3], ARGV[4])
+    "#,
+        &[DEADLINES_KEY.to_string(), poll_id],
+    )
+    .await
 }
 
 pub async fn add_participant_rankings<C>(
@@ -190,51 +404,236 @@ where
     let path = make_rankings_path(user_id);
     let value = serde_json::to_string(&rankings).unwrap();
 
-    let poll_json: String = redis::Script::new(
-        r#"
-        local key = KEYS[1]
-        local path = ARGV[1]
-        local value = ARGV[2]
-        if redis.call('EXISTS', key) == 1 then  
-            if redis.call('JSON.GET', key, '.has_started') == 'true' then
-                redis.call('JSON.SET', key, path, value)
-                return redis.call('JSON.GET', key, '.') 
-            else
-                return '-2'
+    set_ballot(con, key, path, value, BallotType::Ranked).await
+}
+
+pub async fn add_participant_approvals<C>(
+    con: &mut C,
+    poll_id: String,
+    user_id: String,
+    approvals: ApprovalList,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let path = make_approvals_path(user_id);
+    let value = serde_json::to_string(&approvals).unwrap();
+
+    set_ballot(con, key, path, value, BallotType::Approval).await
+}
+
+pub async fn add_participant_scores<C>(
+    con: &mut C,
+    poll_id: String,
+    user_id: String,
+    scores: ScoreList,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let path = make_scores_path(user_id);
+    let value = serde_json::to_string(&scores).unwrap();
+
+    set_ballot(con, key, path, value, BallotType::Score).await
+}
+
+pub async fn add_participant_allocations<C>(
+    con: &mut C,
+    poll_id: String,
+    user_id: String,
+    allocations: AllocationList,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let path = make_allocations_path(user_id);
+    let value = serde_json::to_string(&allocations).unwrap();
+
+    set_ballot(con, key, path, value, BallotType::Cumulative).await
+}
+
+pub async fn start_estimate<C>(
+    con: &mut C,
+    poll_id: String,
+    item: NominationID,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let path = ".estimation".to_string();
+    let estimation = Estimation {
+        item: Some(item),
+        ..Default::default()
+    };
+    let value = serde_json::to_string(&estimation).unwrap();
+
+    set_estimation_value(con, key, path, value, false).await
+}
+
+pub async fn add_card<C>(
+    con: &mut C,
+    poll_id: String,
+    user_id: String,
+    card: String,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id);
+    let path = make_card_path(user_id);
+    let value = serde_json::to_string(&card).unwrap();
+
+    set_estimation_value(con, key, path, value, true).await
+}
+
+/// Times `reveal_cards` reads the cards again after they changed under it.
+const REVEAL_ATTEMPTS: usize = 5;
+
+// The summary is computed here rather than in Lua, so the cards are only
+// revealed if they are still the ones it was computed from.
+pub async fn reveal_cards<C>(con: &mut C, poll_id: String) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id.clone());
+    let kind = serde_json::to_string(&PollKind::Estimation).unwrap();
+    for _ in 0..REVEAL_ATTEMPTS {
+        let poll = get_poll(con, poll_id.clone()).await?;
+        let item = serde_json::to_string(&poll.estimation.item).unwrap();
+        let cards = serde_json::to_string(&poll.estimation.cards).unwrap();
+        let summary = summarize(poll.deck, &poll.estimation.cards);
+        let summary = serde_json::to_string(&summary).unwrap();
+        let path = poll
+            .estimation
+            .item
+            .map(make_estimate_path)
+            .unwrap_or_default();
+
+        let res = update_poll(
+            con,
+            key.clone(),
+            PollAction::Vote,
+            r#"
+        if redis.call('JSON.GET', key, '.kind') ~= ARGV[3] then
+            return '-4'
+        end
+        local item = redis.call('JSON.GET', key, '.estimation.item')
+        if item == 'null' then
+            return '-5'
+        end
+        if redis.call('JSON.GET', key, '.estimation.revealed') == 'true' then
+            return '-6'
+        end
+        if item ~= ARGV[4] then
+            return '-13'
+        end
+        local cards = cjson.decode(redis.call('JSON.GET', key, '.estimation.cards'))
+        local seen = cjson.decode(ARGV[5])
+        for user_id, card in pairs(cards) do
+            if seen[user_id] ~= card then
+                return '-13'
+            end
+        end
+        for user_id, card in pairs(seen) do
+            if cards[user_id] ~= card then
+                return '-13'
             end
-        else
-            return '-1'
         end
+        redis.call('JSON.SET', key, '.estimation.revealed', 'true')
+        redis.call('JSON.SET', key, ARGV[6], ARGV[7])
     "#,
-    )
-    .key(key)
-    .arg(path)
-    .arg(value)
-    .invoke_async(con)
-    .await
-    .map_err(Error::RedisError)?;
-
-    if poll_json == "-1" {
-        return Err(Error::PollNotFound);
+            &[kind.clone(), item, cards, path, summary],
+        )
+        .await;
+        match res {
+            Err(Error::CardsChanged) => continue,
+            res => return res,
+        }
     }
+    Err(Error::CardsChanged)
+}
 
-    if poll_json == "-2" {
-        return Err(Error::PollNoStart);
-    }
+pub async fn add_results<C>(
+    con: &mut C,
+    poll_id: String,
+    results: Results,
+    report: Option<TallyReport>,
+    no_quorum: bool,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id.clone());
+    let results = serde_json::to_string(&results).unwrap();
+    let report = serde_json::to_string(&report).unwrap();
 
-    let poll: Poll = poll_json.try_into()?;
-    Ok(poll)
+    update_poll(
+        con,
+        key,
+        PollAction::Close,
+        r#"
+        redis.call('JSON.SET', key, '.results', ARGV[3])
+        redis.call('JSON.SET', key, '.report', ARGV[4])
+        redis.call('JSON.SET', key, '.no_quorum', ARGV[7])
+        redis.call('ZREM', ARGV[5], ARGV[6])
+    "#,
+        &[
+            results,
+            report,
+            DEADLINES_KEY.to_string(),
+            poll_id,
+            no_quorum.to_string(),
+        ],
+    )
+    .await
 }
 
-pub async fn add_results<C>(con: &mut C, poll_id: String, results: Results) -> Result<Poll, Error>
+pub async fn set_runoff<C>(con: &mut C, poll_id: String, runoff_id: String) -> Result<Poll, Error>
 where
     C: ConnectionLike,
 {
     let key = make_key(poll_id);
-    let path = ".results".to_string();
-    let value = serde_json::to_string(&results).unwrap();
+    let runoff_id = serde_json::to_string(&runoff_id).unwrap();
+
+    update_poll(
+        con,
+        key,
+        PollAction::Runoff,
+        r#"
+        if redis.call('JSON.GET', key, '.runoff_id') ~= 'null' then
+            return '-7'
+        end
+        redis.call('JSON.SET', key, '.runoff_id', ARGV[3])
+    "#,
+        &[runoff_id],
+    )
+    .await
+}
 
-    set_path_value(con, key, path, value).await
+pub async fn cancel_poll<C>(con: &mut C, poll_id: String) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let key = make_key(poll_id.clone());
+    update_poll(
+        con,
+        key,
+        PollAction::Cancel,
+        r#"
+        redis.call('ZREM', ARGV[3], ARGV[5])
+        redis.call('ZREM', ARGV[4], ARGV[5])
+    "#,
+        &[
+            DEADLINES_KEY.to_string(),
+            VOTING_STARTS_KEY.to_string(),
+            poll_id,
+        ],
+    )
+    .await
 }
 
 pub async fn del_poll<C>(con: &mut C, poll_id: String) -> Result<(), Error>
@@ -244,78 +643,261 @@ where
     let key = make_key(poll_id);
     cmd("JSON.DEL")
         .arg(key)
-        .query_async(con)
+        .query_async::<_, ()>(con)
         .await
         .map_err(Error::RedisError)?;
 
     Ok(())
 }
 
-async fn set_path_value<C>(
-    con: &mut C,
-    key: String,
-    path: String,
-    value: String,
-) -> Result<Poll, Error>
+pub async fn voting_starts<C>(con: &mut C) -> Result<Vec<(String, i64)>, Error>
 where
     C: ConnectionLike,
 {
-    let poll_json: String = redis::Script::new(
+    scheduled(con, VOTING_STARTS_KEY).await
+}
+
+pub async fn deadlines<C>(con: &mut C) -> Result<Vec<(String, i64)>, Error>
+where
+    C: ConnectionLike,
+{
+    scheduled(con, DEADLINES_KEY).await
+}
+
+// Members and scores of one of the sorted sets of scheduled polls. Polls that
+// expired or were deleted in the meantime are dropped from the set on the
+// way.
+async fn scheduled<C>(con: &mut C, set_key: &str) -> Result<Vec<(String, i64)>, Error>
+where
+    C: ConnectionLike,
+{
+    let entries: Vec<String> = redis::Script::new(
         r#"
-        local key = KEYS[1]
-        local path = ARGV[1]
-        local value = ARGV[2]
-        if redis.call('EXISTS', key) == 1 then  
-            redis.call('JSON.SET', key, path, value)
-            return redis.call('JSON.GET', key, '.') 
-        else
-            return '-1'
+        local scheduled = {}
+        local entries = redis.call('ZRANGE', KEYS[1], 0, -1, 'WITHSCORES')
+        for i = 1, #entries, 2 do
+            if redis.call('EXISTS', ARGV[1] .. entries[i]) == 1 then
+                table.insert(scheduled, entries[i])
+                table.insert(scheduled, entries[i + 1])
+            else
+                redis.call('ZREM', KEYS[1], entries[i])
+            end
         end
+        return scheduled
     "#,
     )
-    .key(key)
-    .arg(path)
-    .arg(value)
+    .key(set_key)
+    .arg(POLL_KEY_PREFIX)
     .invoke_async(con)
     .await
     .map_err(Error::RedisError)?;
 
-    if poll_json == "-1" {
-        return Err(Error::PollNotFound);
+    let scheduled = entries
+        .chunks(2)
+        .filter_map(|entry| Some((entry[0].clone(), entry.get(1)?.parse().ok()?)))
+        .collect();
+    Ok(scheduled)
+}
+
+// Head of every script changing a poll: the poll must exist and be in one of
+// the phases listed in ARGV[1].
+const CHECK_PHASE: &str = r#"
+        local key = KEYS[1]
+        if redis.call('EXISTS', key) == 0 then
+            return '-1'
+        end
+        local phase = redis.call('JSON.GET', key, '.phase')
+        if not string.find(ARGV[1], phase, 1, true) then
+            return '-2'
+        end
+"#;
+
+// Bails out unless ARGV[3] is the id of a participant.
+const CHECK_PARTICIPANT: &str = r#"
+        local participants = cjson.decode(redis.call('JSON.GET', key, '.participants'))
+        if participants[ARGV[3]] == nil then
+            return '-9'
+        end
+"#;
+
+// Bails out unless ARGV[3] is the id of an invite, which it loads into
+// `invite`.
+const CHECK_INVITE: &str = r#"
+        local invites = cjson.decode(redis.call('JSON.GET', key, '.invites'))
+        local invite = invites[ARGV[3]]
+        if invite == nil then
+            return '-10'
+        end
+"#;
+
+// Tail of every script changing a poll: moves it to the phase in ARGV[2], if
+// any, and returns it.
+const ADVANCE_PHASE: &str = r#"
+        if ARGV[2] ~= '' then
+            redis.call('JSON.SET', key, '.phase', ARGV[2])
+        end
+        return redis.call('JSON.GET', key, '.')
+"#;
+
+// Runs `body` between `CHECK_PHASE` and `ADVANCE_PHASE`, with the phases of
+// `action` from `PollAction::transition`. `args` are ARGV[3] on, and `body`
+// can bail out with the error codes below.
+async fn update_poll<C>(
+    con: &mut C,
+    key: String,
+    action: PollAction,
+    body: &str,
+    args: &[String],
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let (allowed, next) = action.transition();
+    let allowed = serde_json::to_string(allowed).unwrap();
+    let next = next
+        .map(|phase| serde_json::to_string(&phase).unwrap())
+        .unwrap_or_default();
+
+    let script = redis::Script::new(&format!("{CHECK_PHASE}{body}{ADVANCE_PHASE}"));
+    let mut invocation = script.prepare_invoke();
+    invocation.key(key).arg(allowed).arg(next);
+    for arg in args {
+        invocation.arg(arg);
+    }
+    let poll_json: String = invocation
+        .invoke_async(con)
+        .await
+        .map_err(Error::RedisError)?;
+
+    match poll_json.as_str() {
+        "-1" => return Err(Error::PollNotFound),
+        "-2" => return Err(Error::InvalidPhase),
+        "-3" => return Err(Error::WrongBallotType),
+        "-4" => return Err(Error::WrongPollKind),
+        "-5" => return Err(Error::NoEstimateItem),
+        "-6" => return Err(Error::CardsRevealed),
+        "-7" => return Err(Error::RunoffExists),
+        "-8" => return Err(Error::Banned),
+        "-9" => return Err(Error::UnknownParticipant),
+        "-10" => return Err(Error::InviteNotFound),
+        "-11" => return Err(Error::InviteRevoked),
+        "-12" => return Err(Error::InviteExhausted),
+        "-13" => return Err(Error::CardsChanged),
+        _ => {}
     }
 
     let poll: Poll = poll_json.try_into()?;
     Ok(poll)
 }
 
-async fn remove_path_value<C>(con: &mut C, key: String, path: String) -> Result<Poll, Error>
+async fn set_path_value<C>(
+    con: &mut C,
+    key: String,
+    path: String,
+    value: String,
+    action: PollAction,
+) -> Result<Poll, Error>
 where
     C: ConnectionLike,
 {
-    let poll_json: String = redis::Script::new(
+    update_poll(
+        con,
+        key,
+        action,
         r#"
-        local key = KEYS[1]
-        local path = ARGV[1]
-        if redis.call('EXISTS', key) == 1 then  
-            redis.call('JSON.DEL', key, path) 
-            return redis.call('JSON.GET', key, '.') 
-        else
-            return '-1'
+        redis.call('JSON.SET', key, ARGV[3], ARGV[4])
+    "#,
+        &[path, value],
+    )
+    .await
+}
+
+// Sets a ballot of a ranking poll taking this ballot type.
+async fn set_ballot<C>(
+    con: &mut C,
+    key: String,
+    path: String,
+    value: String,
+    ballot_type: BallotType,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let kind = serde_json::to_string(&PollKind::Ranking).unwrap();
+    let ballot_type = serde_json::to_string(&ballot_type).unwrap();
+    update_poll(
+        con,
+        key,
+        PollAction::Vote,
+        r#"
+        if redis.call('JSON.GET', key, '.kind') ~= ARGV[5] then
+            return '-4'
+        end
+        if redis.call('JSON.GET', key, '.ballot_type') ~= ARGV[6] then
+            return '-3'
         end
+        redis.call('JSON.SET', key, ARGV[3], ARGV[4])
     "#,
+        &[path, value, kind, ballot_type],
     )
-    .key(key)
-    .arg(path)
-    .invoke_async(con)
     .await
-    .map_err(Error::RedisError)?;
+}
 
-    if poll_json == "-1" {
-        return Err(Error::PollNotFound);
-    }
+// Sets a value of an estimation poll, and if `needs_open_round`, only while
+// there are cards to play.
+async fn set_estimation_value<C>(
+    con: &mut C,
+    key: String,
+    path: String,
+    value: String,
+    needs_open_round: bool,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    let kind = serde_json::to_string(&PollKind::Estimation).unwrap();
+    update_poll(
+        con,
+        key,
+        PollAction::Vote,
+        r#"
+        if redis.call('JSON.GET', key, '.kind') ~= ARGV[5] then
+            return '-4'
+        end
+        if ARGV[6] == 'true' then
+            if redis.call('JSON.GET', key, '.estimation.item') == 'null' then
+                return '-5'
+            end
+            if redis.call('JSON.GET', key, '.estimation.revealed') == 'true' then
+                return '-6'
+            end
+        end
+        redis.call('JSON.SET', key, ARGV[3], ARGV[4])
+    "#,
+        &[path, value, kind, needs_open_round.to_string()],
+    )
+    .await
+}
 
-    let poll: Poll = poll_json.try_into()?;
-    Ok(poll)
+async fn remove_path_value<C>(
+    con: &mut C,
+    key: String,
+    path: String,
+    action: PollAction,
+) -> Result<Poll, Error>
+where
+    C: ConnectionLike,
+{
+    update_poll(
+        con,
+        key,
+        action,
+        r#"
+        redis.call('JSON.DEL', key, ARGV[3])
+    "#,
+        &[path],
+    )
+    .await
 }
 
 fn make_key(poll_id: String) -> String {
@@ -326,6 +908,10 @@ fn make_participant_path(user_id: String) -> String {
     format!(".participants.{}", user_id)
 }
 
+fn make_invite_path(invite_id: InviteID) -> String {
+    format!(".invites.{}", invite_id)
+}
+
 fn make_nomination_path(nomination_id: NominationID) -> String {
     format!(".nominations.{}", nomination_id)
 }
@@ -334,6 +920,26 @@ fn make_rankings_path(user_id: String) -> String {
     format!(".rankings.{}", user_id)
 }
 
+fn make_approvals_path(user_id: String) -> String {
+    format!(".approvals.{}", user_id)
+}
+
+fn make_scores_path(user_id: String) -> String {
+    format!(".scores.{}", user_id)
+}
+
+fn make_allocations_path(user_id: String) -> String {
+    format!(".allocations.{}", user_id)
+}
+
+fn make_card_path(user_id: String) -> String {
+    format!(".estimation.cards.{}", user_id)
+}
+
+fn make_estimate_path(item: NominationID) -> String {
+    format!(".estimates.{}", item)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -399,6 +1005,7 @@ mod tests {
             "key".to_string(),
             "path".to_string(),
             "value".to_string(),
+            PollAction::Join,
         )
         .await else {
             panic!("should be error but not error")
@@ -428,6 +1035,7 @@ mod tests {
             &mut con,
             "key".to_string(),
             "path".to_string(),
+            PollAction::Leave,
         )
         .await else {
             panic!("should be error but not error")
@@ -453,6 +1061,7 @@ mod tests {
             "key".to_string(),
             "path".to_string(),
             "value".to_string(),
+            PollAction::Join,
         )
         .await else {
             panic!("should be error but not error")
@@ -470,10 +1079,30 @@ mod tests {
             &mut con,
             "key".to_string(),
             "path".to_string(),
+            PollAction::Leave,
         )
         .await else {
             panic!("should be error but not error")
         };
         assert_eq!(err, Error::PollNotFound)
     }
+
+    #[tokio::test]
+    async fn test_update_poll_should_invalid_phase() {
+        let mut con = MockConnectionLike::new();
+        con.mock_returning
+            .expect()
+            .returning(|| Ok("-2".to_string()));
+        let Err(err) = update_poll(
+            &mut con,
+            "key".to_string(),
+            PollAction::Close,
+            "",
+            &[],
+        )
+        .await else {
+            panic!("should be error but not error")
+        };
+        assert_eq!(err, Error::InvalidPhase)
+    }
 }
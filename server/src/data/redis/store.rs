@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, Client};
+
+use crate::{
+    data::PollStore,
+    errors::Error,
+    models::{
+        AllocationList, ApprovalList, Invite, InviteID, Nomination, NominationID, Poll,
+        RankingList, Results, ScoreList,
+    },
+    scoring::TallyReport,
+};
+
+use super::{
+    polls::{self, POLL_KEY_PREFIX},
+    redis_keyspace_notifications,
+};
+
+/// `PollStore` backed by RedisJSON.
+#[derive(Clone)]
+pub struct RedisPollStore {
+    client: Client,
+    con: ConnectionManager,
+}
+
+impl RedisPollStore {
+    pub async fn new(client: Client) -> Result<Self, Error> {
+        let con = ConnectionManager::new(client.clone()).await?;
+        Ok(Self { client, con })
+    }
+}
+
+#[async_trait]
+impl PollStore for RedisPollStore {
+    async fn add_poll(&self, ttl: usize, poll: Poll) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_poll(&mut con, ttl, poll).await
+    }
+
+    async fn get_poll(&self, poll_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::get_poll(&mut con, poll_id).await
+    }
+
+    async fn add_participant(
+        &self,
+        poll_id: String,
+        user_id: String,
+        name: String,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_participant(&mut con, poll_id, user_id, name).await
+    }
+
+    async fn remove_participant(&self, poll_id: String, user_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::remove_participant(&mut con, poll_id, user_id).await
+    }
+
+    async fn ban_participant(&self, poll_id: String, user_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::ban_participant(&mut con, poll_id, user_id).await
+    }
+
+    async fn transfer_admin(&self, poll_id: String, user_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::transfer_admin(&mut con, poll_id, user_id).await
+    }
+
+    async fn set_moderator(
+        &self,
+        poll_id: String,
+        user_id: String,
+        moderator: bool,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::set_moderator(&mut con, poll_id, user_id, moderator).await
+    }
+
+    async fn add_invite(
+        &self,
+        poll_id: String,
+        invite_id: InviteID,
+        invite: Invite,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_invite(&mut con, poll_id, invite_id, invite).await
+    }
+
+    async fn redeem_invite(&self, poll_id: String, invite_id: InviteID) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::redeem_invite(&mut con, poll_id, invite_id).await
+    }
+
+    async fn revoke_invite(&self, poll_id: String, invite_id: InviteID) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::revoke_invite(&mut con, poll_id, invite_id).await
+    }
+
+    async fn add_nomination(
+        &self,
+        poll_id: String,
+        nomination_id: NominationID,
+        nomination: Nomination,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_nomination(&mut con, poll_id, nomination_id, nomination).await
+    }
+
+    async fn remove_nomination(
+        &self,
+        poll_id: String,
+        nomination_id: NominationID,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::remove_nomination(&mut con, poll_id, nomination_id).await
+    }
+
+    async fn open_nominations(&self, poll_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::open_nominations(&mut con, poll_id).await
+    }
+
+    async fn start_poll(&self, poll_id: String, closes_at: Option<i64>) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::start_poll(&mut con, poll_id, closes_at).await
+    }
+
+    async fn clear_deadline(&self, poll_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::clear_deadline(&mut con, poll_id).await
+    }
+
+    async fn add_participant_rankings(
+        &self,
+        poll_id: String,
+        user_id: String,
+        rankings: RankingList,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_participant_rankings(&mut con, poll_id, user_id, rankings).await
+    }
+
+    async fn add_participant_approvals(
+        &self,
+        poll_id: String,
+        user_id: String,
+        approvals: ApprovalList,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_participant_approvals(&mut con, poll_id, user_id, approvals).await
+    }
+
+    async fn add_participant_scores(
+        &self,
+        poll_id: String,
+        user_id: String,
+        scores: ScoreList,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_participant_scores(&mut con, poll_id, user_id, scores).await
+    }
+
+    async fn add_participant_allocations(
+        &self,
+        poll_id: String,
+        user_id: String,
+        allocations: AllocationList,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_participant_allocations(&mut con, poll_id, user_id, allocations).await
+    }
+
+    async fn start_estimate(&self, poll_id: String, item: NominationID) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::start_estimate(&mut con, poll_id, item).await
+    }
+
+    async fn add_card(
+        &self,
+        poll_id: String,
+        user_id: String,
+        card: String,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_card(&mut con, poll_id, user_id, card).await
+    }
+
+    async fn reveal_cards(&self, poll_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::reveal_cards(&mut con, poll_id).await
+    }
+
+    async fn add_results(
+        &self,
+        poll_id: String,
+        results: Results,
+        report: Option<TallyReport>,
+        no_quorum: bool,
+    ) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::add_results(&mut con, poll_id, results, report, no_quorum).await
+    }
+
+    async fn set_runoff(&self, poll_id: String, runoff_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::set_runoff(&mut con, poll_id, runoff_id).await
+    }
+
+    async fn cancel_poll(&self, poll_id: String) -> Result<Poll, Error> {
+        let mut con = self.con.clone();
+        polls::cancel_poll(&mut con, poll_id).await
+    }
+
+    async fn del_poll(&self, poll_id: String) -> Result<(), Error> {
+        let mut con = self.con.clone();
+        polls::del_poll(&mut con, poll_id).await
+    }
+
+    async fn voting_starts(&self) -> Result<Vec<(String, i64)>, Error> {
+        let mut con = self.con.clone();
+        polls::voting_starts(&mut con).await
+    }
+
+    async fn deadlines(&self) -> Result<Vec<(String, i64)>, Error> {
+        let mut con = self.con.clone();
+        polls::deadlines(&mut con).await
+    }
+
+    async fn watch_removed(&self, f: Box<dyn Fn(String) + Send + Sync>) -> anyhow::Result<()> {
+        redis_keyspace_notifications(self.client.clone(), |key| {
+            // other keys, like the deadlines, are deleted once empty
+            if let Some(poll_id) = key.strip_prefix(POLL_KEY_PREFIX) {
+                f(poll_id.to_string())
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::Error,
+    models::{
+        AllocationList, ApprovalList, Invite, InviteID, Nomination, NominationID, Poll,
+        RankingList, Results, ScoreList,
+    },
+    scoring::TallyReport,
+};
+
+pub type DynPollStore = Arc<dyn PollStore>;
+
+/// Storage backend for polls.
+///
+/// Every mutation returns the poll as it is after the change, so callers can
+/// broadcast it to the room without reading it back. Mutations are checked
+/// against the phase of the poll with `PollAction::transition`, and fail with
+/// `Error::InvalidPhase` in any other phase.
+#[async_trait]
+pub trait PollStore: Send + Sync {
+    async fn add_poll(&self, ttl: usize, poll: Poll) -> Result<Poll, Error>;
+
+    async fn get_poll(&self, poll_id: String) -> Result<Poll, Error>;
+
+    /// Fails with `Error::Banned` if the user was banned from the poll.
+    async fn add_participant(
+        &self,
+        poll_id: String,
+        user_id: String,
+        name: String,
+    ) -> Result<Poll, Error>;
+
+    async fn remove_participant(&self, poll_id: String, user_id: String) -> Result<Poll, Error>;
+
+    /// Removes the participant with their ballots and card, and adds them to
+    /// `Poll.banned`.
+    async fn ban_participant(&self, poll_id: String, user_id: String) -> Result<Poll, Error>;
+
+    /// Makes `user_id` the admin, and the previous admin a moderator. Fails
+    /// with `Error::UnknownParticipant` unless `user_id` is a participant.
+    async fn transfer_admin(&self, poll_id: String, user_id: String) -> Result<Poll, Error>;
+
+    /// Adds `user_id` to or removes them from `Poll.moderators`, with the
+    /// same guards as `transfer_admin`.
+    async fn set_moderator(
+        &self,
+        poll_id: String,
+        user_id: String,
+        moderator: bool,
+    ) -> Result<Poll, Error>;
+
+    async fn add_invite(
+        &self,
+        poll_id: String,
+        invite_id: InviteID,
+        invite: Invite,
+    ) -> Result<Poll, Error>;
+
+    /// Counts a use of the invite. Fails with `Error::InviteNotFound`,
+    /// `Error::InviteRevoked` or `Error::InviteExhausted` unless it can still
+    /// be used; its expiry is checked with the token.
+    async fn redeem_invite(&self, poll_id: String, invite_id: InviteID) -> Result<Poll, Error>;
+
+    /// Fails with `Error::InviteNotFound` for an unknown invite.
+    async fn revoke_invite(&self, poll_id: String, invite_id: InviteID) -> Result<Poll, Error>;
+
+    async fn add_nomination(
+        &self,
+        poll_id: String,
+        nomination_id: NominationID,
+        nomination: Nomination,
+    ) -> Result<Poll, Error>;
+
+    async fn remove_nomination(
+        &self,
+        poll_id: String,
+        nomination_id: NominationID,
+    ) -> Result<Poll, Error>;
+
+    async fn open_nominations(&self, poll_id: String) -> Result<Poll, Error>;
+
+    /// Starts voting, closing the poll at `closes_at`, a unix timestamp in
+    /// milliseconds, if set.
+    async fn start_poll(&self, poll_id: String, closes_at: Option<i64>) -> Result<Poll, Error>;
+
+    /// Drops `Poll.closes_at`, so the poll stays open until closed by hand.
+    async fn clear_deadline(&self, poll_id: String) -> Result<Poll, Error>;
+
+    /// Fails with `Error::WrongPollKind` unless the poll is a ranking poll,
+    /// and with `Error::WrongBallotType` unless it takes ranked ballots.
+    async fn add_participant_rankings(
+        &self,
+        poll_id: String,
+        user_id: String,
+        rankings: RankingList,
+    ) -> Result<Poll, Error>;
+
+    /// Same guards as `add_participant_rankings`, for approval ballots.
+    async fn add_participant_approvals(
+        &self,
+        poll_id: String,
+        user_id: String,
+        approvals: ApprovalList,
+    ) -> Result<Poll, Error>;
+
+    /// Same guards as `add_participant_rankings`, for score ballots.
+    async fn add_participant_scores(
+        &self,
+        poll_id: String,
+        user_id: String,
+        scores: ScoreList,
+    ) -> Result<Poll, Error>;
+
+    /// Same guards as `add_participant_rankings`, for cumulative ballots.
+    /// Callers check the ballot against the credit budget.
+    async fn add_participant_allocations(
+        &self,
+        poll_id: String,
+        user_id: String,
+        allocations: AllocationList,
+    ) -> Result<Poll, Error>;
+
+    /// Starts a new estimation round on `item`. Fails with
+    /// `Error::WrongPollKind` unless the poll is an estimation poll.
+    async fn start_estimate(&self, poll_id: String, item: NominationID) -> Result<Poll, Error>;
+
+    /// Same guards as `start_estimate`, and fails with `Error::NoEstimateItem`
+    /// or `Error::CardsRevealed` unless a round is open.
+    async fn add_card(&self, poll_id: String, user_id: String, card: String)
+        -> Result<Poll, Error>;
+
+    /// Closes the open round, with the same guards as `add_card`, and adds
+    /// the summary of its cards to `Poll.estimates` in the same step, so no
+    /// card can be played in between.
+    async fn reveal_cards(&self, poll_id: String) -> Result<Poll, Error>;
+
+    /// Closes the poll with its results, marked `Poll.no_quorum` if
+    /// `no_quorum`.
+    async fn add_results(
+        &self,
+        poll_id: String,
+        results: Results,
+        report: Option<TallyReport>,
+        no_quorum: bool,
+    ) -> Result<Poll, Error>;
+
+    /// Links a closed poll to its runoff. Fails with `Error::RunoffExists` if
+    /// it already has one.
+    async fn set_runoff(&self, poll_id: String, runoff_id: String) -> Result<Poll, Error>;
+
+    /// Marks the poll cancelled. It stays, read only, until it expires.
+    async fn cancel_poll(&self, poll_id: String) -> Result<Poll, Error>;
+
+    async fn del_poll(&self, poll_id: String) -> Result<(), Error>;
+
+    /// Ids and `Poll.voting_starts_at` of the polls voting has not started
+    /// in yet. Kept in the store like `PollStore::deadlines`.
+    async fn voting_starts(&self) -> Result<Vec<(String, i64)>, Error>;
+
+    /// Ids and `Poll.closes_at` of the polls being voted on with a deadline.
+    /// Deadlines are only kept in the store, so they survive a restart.
+    async fn deadlines(&self) -> Result<Vec<(String, i64)>, Error>;
+
+    /// Runs until the backend fails, calling `f` with the id of every poll
+    /// that is deleted or expires.
+    async fn watch_removed(&self, f: Box<dyn Fn(String) + Send + Sync>) -> anyhow::Result<()>;
+}
@@ -18,17 +18,104 @@ pub enum Error {
     #[error(transparent)]
     RedisError(#[from] redis::RedisError),
 
+    #[error(transparent)]
+    EmbeddedError(Box<redb::Error>),
+
     #[error("Poll not found")]
     PollNotFound,
 
     #[error("Poll cancelled")]
     PollCancelled,
 
-    #[error("Poll no start")]
-    PollNoStart,
+    #[error("Invalid poll phase")]
+    InvalidPhase,
+
+    #[error("Wrong ballot type")]
+    WrongBallotType,
+
+    #[error("Wrong poll kind")]
+    WrongPollKind,
+
+    #[error("No item to estimate")]
+    NoEstimateItem,
+
+    #[error("Cards revealed")]
+    CardsRevealed,
+
+    #[error("Cards changed while revealing")]
+    CardsChanged,
+
+    #[error("Unknown card")]
+    UnknownCard,
+
+    #[error("Empty ballot")]
+    EmptyBallot,
+
+    #[error("Nomination ranked more than once")]
+    DuplicateRanking,
+
+    #[error("Ballot ranks more than {max} nominations")]
+    TooManyRankings { max: usize },
+
+    #[error("Ballot must rank exactly {expected} nominations")]
+    WrongRankingCount { expected: usize },
+
+    #[error("Poll not open yet")]
+    PollNotOpen,
+
+    #[error("Voting can not start before the poll opens")]
+    InvalidSchedule,
+
+    #[error("Instant-runoff, single transferable vote and STAR can not keep ties")]
+    CannotKeepTies,
+
+    #[error("Only {voted} voted, {required} needed for quorum")]
+    NoQuorum { voted: usize, required: usize },
+
+    #[error("A runoff needs at least two nominations")]
+    RunoffTooSmall,
 
-    #[error("Poll has started")]
-    PollHasStarted,
+    #[error("You were removed from this poll")]
+    Banned,
+
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+
+    #[error("Too many failed attempts, try again later")]
+    TooManyAttempts,
+
+    #[error("Passphrase hashing error")]
+    PassphraseHashing,
+
+    #[error("Invite not found")]
+    InviteNotFound,
+
+    #[error("Invite revoked")]
+    InviteRevoked,
+
+    #[error("Invite used up")]
+    InviteExhausted,
+
+    #[error("Invite expired")]
+    InviteExpired,
+
+    #[error("Name required")]
+    NameRequired,
+
+    #[error("Admin can not be removed")]
+    CannotRemoveAdmin,
+
+    #[error("Runoff already started")]
+    RunoffExists,
+
+    #[error("Deadline in the past")]
+    DeadlineInPast,
+
+    #[error("Score out of range")]
+    ScoreOutOfRange,
+
+    #[error("Ballot costs {cost} credits, over the budget of {budget}")]
+    OverBudget { cost: usize, budget: usize },
 
     #[error("Wrong credentials")]
     WrongCredentials,
@@ -51,6 +138,18 @@ pub enum Error {
     #[error("Admin privileges required")]
     AdminPrivilegesRequired,
 
+    #[error("Moderator privileges required")]
+    ModeratorPrivilegesRequired,
+
+    #[error("Admin can only hand over their role")]
+    CannotDemoteAdmin,
+
+    #[error("Spectators can only watch")]
+    SpectatorReadOnly,
+
+    #[error("Unknown participant")]
+    UnknownParticipant,
+
     #[error("Unknown nomination")]
     UnknownNomination,
 
@@ -9,6 +9,7 @@ use serde::Serialize;
 
 pub mod not_found;
 pub mod polls;
+pub mod schedule;
 pub mod sse;
 pub mod ws;
 
@@ -38,7 +39,29 @@ impl IntoResponse for Error {
                     "Internal server error".to_string(),
                 )
             }
+            Error::EmbeddedError(_) => {
+                tracing::error!(error = %self, "Embedded store error");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    500,
+                    "Internal server error".to_string(),
+                )
+            }
             Error::PollNotFound => (StatusCode::BAD_REQUEST, 500, "Poll not found".to_string()),
+            Error::InvalidPhase => (StatusCode::BAD_REQUEST, 510, self.to_string()),
+            Error::PollNotOpen => (StatusCode::FORBIDDEN, 520, self.to_string()),
+            Error::InvalidSchedule => (StatusCode::BAD_REQUEST, 530, self.to_string()),
+            Error::CannotKeepTies => (StatusCode::BAD_REQUEST, 535, self.to_string()),
+            Error::Banned => (StatusCode::FORBIDDEN, 540, self.to_string()),
+            Error::WrongPassphrase => (StatusCode::UNAUTHORIZED, 550, self.to_string()),
+            Error::TooManyAttempts => (StatusCode::TOO_MANY_REQUESTS, 560, self.to_string()),
+            Error::PassphraseHashing => (StatusCode::INTERNAL_SERVER_ERROR, 570, self.to_string()),
+            Error::InviteNotFound => (StatusCode::BAD_REQUEST, 580, self.to_string()),
+            Error::InviteRevoked => (StatusCode::FORBIDDEN, 581, self.to_string()),
+            Error::InviteExhausted => (StatusCode::FORBIDDEN, 582, self.to_string()),
+            Error::InviteExpired => (StatusCode::FORBIDDEN, 583, self.to_string()),
+            Error::NameRequired => (StatusCode::BAD_REQUEST, 590, self.to_string()),
+            Error::AdminPrivilegesRequired => (StatusCode::FORBIDDEN, 595, self.to_string()),
 
             Error::WrongCredentials => (
                 StatusCode::UNAUTHORIZED,
@@ -57,8 +80,9 @@ impl IntoResponse for Error {
             ),
             Error::InvalidToken => (StatusCode::BAD_REQUEST, 900, "Invalid token".to_string()),
 
-            // websocket error!
-            _ => unreachable!(),
+            // the rest come from the websocket, where the client broke a rule
+            // of the poll
+            _ => (StatusCode::BAD_REQUEST, 1000, self.to_string()),
         };
         (
             status_code,
@@ -100,3 +124,14 @@ where
         Json(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_response_should_map_websocket_errors() {
+        let response = Error::EmptyBallot.into_response();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+}
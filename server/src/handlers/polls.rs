@@ -1,36 +1,82 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
-use axum::{extract::State, Extension, Json};
-use redis::aio::ConnectionManager;
+use axum::{
+    extract::{ConnectInfo, State},
+    Extension, Json,
+};
 
 use crate::{
     auth::{self, Authed},
-    data::redis::polls,
+    data::DynPollStore,
     errors::Error,
-    handlers::UnifyResponse,
-    models::{AddPollReq, AddPollResp, JoinPollReq, JoinPollResp, Notification, NotifyType, Poll},
-    shared::ids::{create_poll_id, create_user_id},
+    handlers::{ws, UnifyResponse},
+    models::{
+        AddInviteReq, AddInviteResp, AddPollReq, AddPollResp, Invite, JoinPollReq, JoinPollResp,
+        Notification, NotifyType, Poll, PollAction, RedeemInviteReq, RevokeInviteReq, Role,
+    },
+    scoring::TieBreak,
+    shared::{
+        ids::{create_invite_id, create_poll_id, create_user_id},
+        passphrase,
+    },
     state::AppState,
     validate::Input,
 };
 
 pub async fn add(
     State(state): State<Arc<AppState>>,
-    Extension(mut con): Extension<ConnectionManager>,
+    Extension(store): Extension<DynPollStore>,
     Input(input): Input<AddPollReq>,
 ) -> Result<Json<UnifyResponse<AddPollResp>>, Error> {
-    let ttl = state.env.poll_duration;
+    if let (Some(open_at), Some(voting_starts_at)) = (input.open_at, input.voting_starts_at) {
+        if voting_starts_at < open_at {
+            return Err(Error::InvalidSchedule);
+        }
+    }
+    let tie_break = match input.tie_break {
+        Some(TieBreak::KeepTied) if input.counts_in_rounds() => return Err(Error::CannotKeepTies),
+        Some(tie_break) => tie_break,
+        None if input.counts_in_rounds() => TieBreak::FirstPlaces,
+        None => TieBreak::KeepTied,
+    };
+    // the poll lasts `poll_duration` from the latest scheduled time on
+    let scheduled_ms = input
+        .voting_starts_at
+        .or(input.open_at)
+        .map_or(0, |at| at - chrono::Utc::now().timestamp_millis())
+        .max(0);
+    let ttl = state.env.poll_duration + (scheduled_ms as usize).div_ceil(1000);
+    let passphrase_hash = match input.passphrase {
+        Some(passphrase) => Some(passphrase::hash(passphrase).await?),
+        None => None,
+    };
     let poll_id = create_poll_id();
     let user_id = create_user_id();
-    let poll = polls::add_poll(
-        &mut con,
-        ttl,
-        poll_id.clone(),
-        input.topic.clone(),
-        input.votes_per_voter,
-        user_id.clone(),
-    )
-    .await?;
+    let poll = Poll {
+        kind: input.kind,
+        ballot_type: input.ballot_type,
+        scoring_method: input.scoring_method,
+        rank_exactly: input.rank_exactly,
+        seats: input.seats,
+        credit_budget: input.credit_budget,
+        credit_cost: input.credit_cost,
+        deck: input.deck,
+        tie_break,
+        tie_break_seed: (tie_break == TieBreak::Random).then(rand::random),
+        secret_ballots: input.secret_ballots,
+        close_when_all_voted: input.close_when_all_voted,
+        quorum: input.quorum,
+        open_at: input.open_at,
+        voting_starts_at: input.voting_starts_at,
+        passphrase_hash,
+        ..Poll::new(
+            poll_id.clone(),
+            input.topic.clone(),
+            input.votes_per_voter,
+            user_id.clone(),
+        )
+    };
+    let poll = store.add_poll(ttl, poll).await?.view_for(&user_id);
     let access_token = auth::token_gen(poll_id.clone(), user_id.clone(), input.name.clone(), ttl)?;
     let add_poll_resp = AddPollResp { poll, access_token };
 
@@ -44,25 +90,213 @@ pub async fn add(
     Ok(UnifyResponse::ok(Some(add_poll_resp)).json())
 }
 
+/// Joins as a new participant, see `check_joinable`.
 pub async fn join(
     State(state): State<Arc<AppState>>,
-    Extension(mut con): Extension<ConnectionManager>,
+    Extension(store): Extension<DynPollStore>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    authed: Option<Authed>,
     Input(input): Input<JoinPollReq>,
 ) -> Result<Json<UnifyResponse<JoinPollResp>>, Error> {
     let ttl = state.env.poll_duration;
     let user_id = create_user_id();
     let poll_id = input.poll_id;
-    let poll = polls::get_poll(&mut con, poll_id.clone()).await?;
+    let poll = store.get_poll(poll_id.clone()).await?;
+    check_joinable(&poll, authed.as_ref())?;
+    check_passphrase(&state, &poll, input.passphrase, addr).await?;
+    let poll = poll.view_for(&user_id);
 
     let access_token = auth::token_gen(poll_id, user_id, input.name, ttl)?;
     let join_poll_resp = JoinPollResp { poll, access_token };
     Ok(UnifyResponse::ok(Some(join_poll_resp)).json())
 }
 
+/// Joins with an invite link instead of the poll id, and its passphrase if
+/// any. Uses of the invite are counted by the store.
+///
+/// Invite tokens that do not verify count against the address like wrong
+/// passphrases do, see `check_passphrase`.
+pub async fn join_with_invite(
+    State(state): State<Arc<AppState>>,
+    Extension(store): Extension<DynPollStore>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    authed: Option<Authed>,
+    Input(input): Input<RedeemInviteReq>,
+) -> Result<Json<UnifyResponse<JoinPollResp>>, Error> {
+    let ttl = state.env.poll_duration;
+    let throttle = &state.join_throttle;
+    let ip_key = format!("ip:{}", addr.ip());
+    throttle.try_acquire(&ip_key, state.env.join_failures_per_ip)?;
+    let invite = auth::verify_invite(input.invite_token);
+    if !matches!(invite, Err(Error::InvalidToken)) {
+        throttle.refund(&ip_key);
+    }
+    let invite = invite?;
+    let name = invite.name.or(input.name).ok_or(Error::NameRequired)?;
+    let user_id = create_user_id();
+    let poll_id = invite.poll_id;
+    let poll = store.get_poll(poll_id.clone()).await?;
+    check_joinable(&poll, authed.as_ref())?;
+    check_passphrase(&state, &poll, input.passphrase, addr).await?;
+    let poll = store
+        .redeem_invite(poll_id.clone(), invite.invite_id)
+        .await?
+        .view_for(&user_id);
+
+    let access_token = auth::token_gen(poll_id, user_id, name, ttl)?;
+    let join_poll_resp = JoinPollResp { poll, access_token };
+    Ok(UnifyResponse::ok(Some(join_poll_resp)).json())
+}
+
+/// Hands out an invite link to the poll of the admin.
+pub async fn add_invite(
+    Extension(store): Extension<DynPollStore>,
+    authed: Authed,
+    Input(input): Input<AddInviteReq>,
+) -> Result<Json<UnifyResponse<AddInviteResp>>, Error> {
+    let poll = store.get_poll(authed.poll_id).await?;
+    poll.require(&authed.sub, Role::Admin)?;
+    let invite_id = create_invite_id();
+    let invite = Invite {
+        max_uses: input.max_uses,
+        uses: 0,
+        name: input.name.clone(),
+        expires_at: chrono::Utc::now().timestamp() + input.expires_in as i64,
+        revoked: false,
+    };
+    store
+        .add_invite(poll.id.clone(), invite_id.clone(), invite)
+        .await?;
+
+    let invite_token =
+        auth::invite_token_gen(poll.id, invite_id.clone(), input.name, input.expires_in)?;
+    let add_invite_resp = AddInviteResp {
+        invite_id,
+        invite_token,
+    };
+    Ok(UnifyResponse::ok(Some(add_invite_resp)).json())
+}
+
+pub async fn revoke_invite(
+    Extension(store): Extension<DynPollStore>,
+    authed: Authed,
+    Input(input): Input<RevokeInviteReq>,
+) -> Result<Json<UnifyResponse<Poll>>, Error> {
+    let poll = store.get_poll(authed.poll_id).await?;
+    poll.require(&authed.sub, Role::Admin)?;
+    let poll = store
+        .revoke_invite(poll.id, input.invite_id)
+        .await?
+        .view_for(&authed.sub);
+    Ok(UnifyResponse::ok(Some(poll)).json())
+}
+
+/// Watches the poll with a spectator token, without joining it.
+pub async fn spectate(
+    State(state): State<Arc<AppState>>,
+    Extension(store): Extension<DynPollStore>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    authed: Option<Authed>,
+    Input(input): Input<JoinPollReq>,
+) -> Result<Json<UnifyResponse<JoinPollResp>>, Error> {
+    let ttl = state.env.poll_duration;
+    let user_id = create_user_id();
+    let poll_id = input.poll_id;
+    let poll = store.get_poll(poll_id.clone()).await?;
+    check_banned(&poll, authed.as_ref())?;
+    check_passphrase(&state, &poll, input.passphrase, addr).await?;
+    let poll = poll.view_for(&user_id);
+
+    let access_token = auth::spectator_token_gen(poll_id, user_id, input.name, ttl)?;
+    let join_poll_resp = JoinPollResp { poll, access_token };
+    Ok(UnifyResponse::ok(Some(join_poll_resp)).json())
+}
+
+/// Fails if the poll is not open yet, with `Error::InvalidPhase` if its phase
+/// does not allow joining, or with `check_banned`.
+pub fn check_joinable(poll: &Poll, authed: Option<&Authed>) -> Result<(), Error> {
+    if poll
+        .open_at
+        .is_some_and(|open_at| open_at > chrono::Utc::now().timestamp_millis())
+    {
+        return Err(Error::PollNotOpen);
+    }
+    // the store refuses it too, but only once the socket connects
+    let mut phase = poll.phase;
+    phase.apply(PollAction::Join)?;
+    check_banned(poll, authed)
+}
+
+/// Fails if `authed` was banned from the poll. A banned user is only refused
+/// if they send their previous token along, since nothing else tells them
+/// apart.
+fn check_banned(poll: &Poll, authed: Option<&Authed>) -> Result<(), Error> {
+    if authed.is_some_and(|authed| authed.poll_id == poll.id && poll.banned.contains(&authed.sub)) {
+        return Err(Error::Banned);
+    }
+    Ok(())
+}
+
+/// Fails unless `passphrase` is the one `poll` was created with, if any.
+/// Attempts are counted per poll and per address before the passphrase is
+/// checked, and given back unless it was wrong. Once the address is at its
+/// limit, attempts are refused before being checked. Once the poll is, they
+/// are only slowed down by `state.env.join_failure_delay`, so that strangers
+/// guessing its passphrase do not lock its participants out.
+async fn check_passphrase(
+    state: &AppState,
+    poll: &Poll,
+    passphrase: Option<String>,
+    addr: SocketAddr,
+) -> Result<(), Error> {
+    let Some(hash) = poll.passphrase_hash.clone() else {
+        return Ok(());
+    };
+    let throttle = &state.join_throttle;
+    let poll_key = format!("poll:{}", poll.id);
+    let ip_key = format!("ip:{}", addr.ip());
+    throttle.try_acquire(&ip_key, state.env.join_failures_per_ip)?;
+    let poll_counted = throttle
+        .try_acquire(&poll_key, state.env.join_failures_per_poll)
+        .is_ok();
+    if !poll_counted {
+        tokio::time::sleep(Duration::from_millis(state.env.join_failure_delay)).await;
+    }
+
+    let verified = match passphrase {
+        Some(passphrase) => passphrase::verify(passphrase, hash).await,
+        None => Ok(false),
+    };
+    // only wrong passphrases count against the limits
+    if !matches!(verified, Ok(false)) {
+        if poll_counted {
+            throttle.refund(&poll_key);
+        }
+        throttle.refund(&ip_key);
+    }
+    match verified? {
+        true => Ok(()),
+        false => Err(Error::WrongPassphrase),
+    }
+}
+
 pub async fn rejoin(
-    Extension(mut con): Extension<ConnectionManager>,
+    Extension(store): Extension<DynPollStore>,
     authed: Authed,
 ) -> Result<Json<UnifyResponse<Poll>>, Error> {
-    let poll = polls::add_participant(&mut con, authed.poll_id, authed.sub, authed.name).await?;
+    if authed.spectator {
+        let poll = store.get_poll(authed.poll_id.clone()).await?;
+        check_banned(&poll, Some(&authed))?;
+        let poll = poll.view_for(&authed.sub);
+        return Ok(UnifyResponse::ok(Some(poll)).json());
+    }
+    let poll = ws::reconnect(
+        store.as_ref(),
+        authed.poll_id,
+        authed.sub.clone(),
+        authed.name,
+    )
+    .await?
+    .view_for(&authed.sub);
     Ok(UnifyResponse::ok(Some(poll)).json())
 }
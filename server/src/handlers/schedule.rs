@@ -0,0 +1,107 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    data::{DynPollStore, PollStore},
+    errors::Error,
+    models::{room::Rooms, Poll, PollPhase, WebSocketEvent},
+};
+
+use super::ws::close;
+
+/// How often the schedule is checked, and countdowns sent.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Starts voting at `Poll.voting_starts_at` and closes polls at their
+/// deadline, sending every room with one the seconds left on each tick.
+///
+/// The schedule is read back from the store on every tick, so what was
+/// scheduled before a restart still happens. A store that fails to read is
+/// tried again on the next tick.
+pub async fn run(store: DynPollStore, rooms: Arc<Rooms>) -> anyhow::Result<()> {
+    let mut interval = tokio::time::interval(TICK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let voting_starts = store.voting_starts().await.unwrap_or_else(|err| {
+            tracing::warn!(error = %err, "Reading scheduled votes");
+            Vec::new()
+        });
+        for (poll_id, starts_at) in voting_starts {
+            if starts_at > now {
+                continue;
+            }
+            match start_voting(store.as_ref(), poll_id.clone()).await {
+                Ok(None) => {}
+                Ok(Some(poll)) => {
+                    rooms
+                        .broadcast(poll_id, WebSocketEvent::PollUpdated(Box::new(poll)))
+                        .await;
+                }
+                Err(err) => tracing::warn!(%poll_id, error = %err, "Starting scheduled vote"),
+            }
+        }
+
+        let deadlines = store.deadlines().await.unwrap_or_else(|err| {
+            tracing::warn!(error = %err, "Reading deadlines");
+            Vec::new()
+        });
+        for (poll_id, closes_at) in deadlines {
+            if closes_at > now {
+                let seconds_left = (closes_at - now + 999) / 1000;
+                rooms
+                    .broadcast(poll_id, WebSocketEvent::Countdown(seconds_left))
+                    .await;
+                continue;
+            }
+
+            match close_at_deadline(store.as_ref(), &rooms, poll_id.clone()).await {
+                Ok(poll) => {
+                    rooms
+                        .broadcast(poll_id, WebSocketEvent::PollUpdated(Box::new(poll)))
+                        .await;
+                }
+                // closed or deleted since the deadlines were read
+                Err(err) => tracing::warn!(%poll_id, error = %err, "Closing poll at deadline"),
+            }
+        }
+    }
+}
+
+/// Closes a poll at its deadline. Below quorum it is left open, without a
+/// deadline, for the admin to force close or cancel, and the room is told
+/// why.
+pub async fn close_at_deadline(
+    store: &dyn PollStore,
+    rooms: &Rooms,
+    poll_id: String,
+) -> Result<Poll, Error> {
+    let poll = store.get_poll(poll_id.clone()).await?;
+    match close(store, poll, false).await {
+        Err(err @ Error::NoQuorum { .. }) => {
+            rooms
+                .broadcast(poll_id.clone(), WebSocketEvent::Exception(err.to_string()))
+                .await;
+            store.clear_deadline(poll_id).await
+        }
+        closed => closed,
+    }
+}
+
+/// Moves a scheduled poll on to voting, through nominations if it is still
+/// in the lobby. Without nominations it waits in `PollPhase::Nominating`, and
+/// voting starts on the first tick after one is added.
+///
+/// Returns the poll if it changed.
+async fn start_voting(store: &dyn PollStore, poll_id: String) -> Result<Option<Poll>, Error> {
+    let poll = store.get_poll(poll_id.clone()).await?;
+    let opened = poll.phase == PollPhase::Lobby;
+    let poll = match opened {
+        true => store.open_nominations(poll_id.clone()).await?,
+        false => poll,
+    };
+    if poll.nominations.is_empty() {
+        return Ok(opened.then_some(poll));
+    }
+    store.start_poll(poll_id, None).await.map(Some)
+}
@@ -7,11 +7,14 @@ use axum::{
     response::IntoResponse,
     Extension,
 };
-use redis::aio::{ConnectionLike, ConnectionManager};
 use tokio::sync::Mutex;
 use validator::Validate;
 
-use std::{cell::RefCell, sync::Arc};
+use std::{
+    cell::RefCell,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 // allows to extract the IP of connecting user
 use axum::extract::connect_info::ConnectInfo;
@@ -25,25 +28,27 @@ use futures::{
 
 use crate::{
     auth::{self, Authed},
-    data::redis::polls,
+    data::{DynPollStore, PollStore},
     errors::Error,
     models::{
         room::{RoomClient, Rooms},
-        AddNominationReq, Nomination, Poll, RankingList, WebSocketEvent,
+        AddNominationReq, AllocationList, ApprovalList, JoinPollResp, Nomination, NominationID,
+        Poll, PollKind, PollPhase, RankingList, Role, ScoreList, UserID, WebSocketEvent,
     },
-    shared::ids::create_nomination_id,
+    scoring::MAX_SCORE,
+    shared::ids::{create_connection_id, create_nomination_id, create_poll_id},
     state::AppState,
 };
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
-    Extension(con): Extension<ConnectionManager>,
+    Extension(store): Extension<DynPollStore>,
     State(state): State<Arc<AppState>>,
     authorization: Option<TypedHeader<headers::Authorization<Bearer>>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> impl IntoResponse {
     let Some(TypedHeader(token)) = authorization else {
-        return Error::MissingCredentials.into_response()
+        return Error::MissingCredentials.into_response();
     };
 
     let token = token.0.token();
@@ -51,14 +56,13 @@ pub async fn ws_handler(
         return Error::InvalidToken.into_response();
     };
 
-    let con = con.clone();
-    ws.on_upgrade(move |socket| handle_socket(socket, con, auth, state, addr))
+    ws.on_upgrade(move |socket| handle_socket(socket, store, auth, state, addr))
 }
 
 /// Actual websocket statemachine (one will be spawned per connection)
 async fn handle_socket(
     socket: WebSocket,
-    mut con: ConnectionManager,
+    store: DynPollStore,
     auth: Authed,
     state: Arc<AppState>,
     addr: SocketAddr,
@@ -66,28 +70,57 @@ async fn handle_socket(
     let user_id = auth.sub.clone();
     let poll_id = auth.poll_id.clone();
     let name = auth.name;
+    let spectator = auth.spectator;
+    let ttl = state.env.poll_duration;
+    // Poll the socket is in, which changes when the room moves to a runoff.
+    let current_poll_id = Arc::new(RwLock::new(poll_id.clone()));
 
-    let Ok(poll) =
-        polls::add_participant(&mut con, poll_id.clone(), user_id.clone(), name.clone()).await else {
-        return;
+    // spectators watch without joining
+    let poll = if spectator {
+        match store.get_poll(poll_id.clone()).await {
+            Ok(poll) if poll.banned.contains(&user_id) => Err(Error::Banned),
+            poll => poll,
+        }
+    } else {
+        reconnect(
+            store.as_ref(),
+            poll_id.clone(),
+            user_id.clone(),
+            name.clone(),
+        )
+        .await
+    };
+    let poll = match poll {
+        Ok(poll) => poll,
+        Err(Error::Banned) => {
+            let mut socket = socket;
+            let _ = socket
+                .send(Message::Text(WebSocketEvent::Removed.message()))
+                .await;
+            let _ = socket.close().await;
+            return;
+        }
+        Err(_) => return,
     };
 
     let rooms = state.rooms.clone();
+    let connection_id = create_connection_id();
     let mut vote = rooms
         .add_client(
             poll_id.clone(),
+            connection_id.clone(),
             RoomClient {
                 id: user_id.clone(),
                 addr: addr.to_string(),
                 name: name.clone(),
                 join_time: chrono::Utc::now().timestamp(),
+                spectator,
             },
         )
         .await;
 
     // Now send the "joined" message to all subscribers.
-    let msg = WebSocketEvent::PollUpdated(Box::new(poll)).message();
-    vote.broadcast(msg);
+    vote.broadcast(WebSocketEvent::PollUpdated(Box::new(poll)));
 
     // By splitting, we can send and receive at the same time.
     let (sender, mut receiver) = socket.split();
@@ -97,11 +130,43 @@ async fn handle_socket(
     let mut rx = vote.subscribe();
 
     let server_sender = sender.clone();
+    let viewer_id = user_id.clone();
+    let current = current_poll_id.clone();
     // server -> client
     // Spawn the first task that will receive broadcast messages and send text
     // messages over the websocket to our client.
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+        while let Ok(event) = rx.recv().await {
+            let event = match event {
+                WebSocketEvent::RunoffStarted(poll) => {
+                    *current.write().unwrap() = poll.id.clone();
+                    let token_gen = match spectator {
+                        true => auth::spectator_token_gen,
+                        false => auth::token_gen,
+                    };
+                    let Ok(access_token) =
+                        token_gen(poll.id.clone(), viewer_id.clone(), name.clone(), ttl)
+                    else {
+                        break;
+                    };
+                    WebSocketEvent::MovedToRunoff(Box::new(JoinPollResp {
+                        poll: *poll,
+                        access_token,
+                    }))
+                }
+                WebSocketEvent::ParticipantRemoved(removed_id) if removed_id == viewer_id => {
+                    let _ = send_message(server_sender.clone(), WebSocketEvent::Removed.message())
+                        .await;
+                    let _ = server_sender.lock().await.get_mut().close().await;
+                    break;
+                }
+                event => event,
+            };
+            if !event.is_for(&viewer_id) {
+                continue;
+            }
+            // Every client gets its own view of the poll.
+            let msg = event.view_for(&viewer_id).message();
             // In any websocket error, break loop.
             if !send_message(server_sender.clone(), msg).await {
                 break;
@@ -110,95 +175,234 @@ async fn handle_socket(
     });
 
     // notify
-    let room_id_clone = poll_id.clone();
+    let current = current_poll_id.clone();
     let mut rx = state.notify_tx.subscribe();
     let mut notify_task = tokio::spawn(async move {
         while let Ok(room_id) = rx.recv().await {
-            if room_id_clone == room_id {
+            if *current.read().unwrap() == room_id {
                 break;
             }
         }
 
         // TODO: how to delete room cache
-        rooms.remove(room_id_clone).await;
+        let room_id = current.read().unwrap().clone();
+        rooms.remove(room_id).await;
     });
 
     let client_sender = sender.clone();
+    let (leaver_id, leaver_store, left_poll_id, left_connection_id) = (
+        user_id.clone(),
+        store.clone(),
+        current_poll_id.clone(),
+        connection_id.clone(),
+    );
     // Clone things we want to pass (move) to the receiving task.
     let mut tx = vote.clone();
-    let poll_id = poll_id.clone();
     let rooms = state.rooms.clone();
+    let notify_tx = state.notify_tx.clone();
     // client -> server
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
+            let poll_id = current_poll_id.read().unwrap().clone();
             match msg {
+                Message::Close(_) if spectator => {
+                    rooms
+                        .remove_client(poll_id.clone(), connection_id.clone())
+                        .await;
+                    break;
+                }
                 Message::Close(_) => {
-                    rooms.remove_client(poll_id.clone(), user_id.clone()).await;
+                    rooms
+                        .remove_client(poll_id.clone(), connection_id.clone())
+                        .await;
                     // listen client exit
-                    let Ok(poll) = polls::remove_participant(&mut con, poll_id.clone(), user_id.clone()).await else {
+                    let Ok(poll) = store
+                        .remove_participant(poll_id.clone(), user_id.clone())
+                        .await
+                    else {
                         // just exit if err
                         break;
                     };
-                    let message = WebSocketEvent::PollUpdated(Box::new(poll)).message();
-                    tx.broadcast(message);
+                    tx.broadcast(WebSocketEvent::PollUpdated(Box::new(poll)));
                     break;
                 }
+                Message::Text(_) if spectator => {
+                    let message = WebSocketEvent::Exception(Error::SpectatorReadOnly.to_string());
+                    if !send_message(client_sender.clone(), message.message()).await {
+                        break;
+                    }
+                }
                 Message::Text(text) => {
                     let event: WebSocketEvent = text.into();
+                    let is_ballot = matches!(
+                        event,
+                        WebSocketEvent::SubmitRankings(_)
+                            | WebSocketEvent::SubmitApprovals(_)
+                            | WebSocketEvent::SubmitScores(_)
+                            | WebSocketEvent::SubmitAllocations(_)
+                    );
                     let event: Result<Poll, Error> = match event {
-                        WebSocketEvent::RemoveParticipant(user_id) => {
-                            polls::remove_participant(&mut con, poll_id.clone(), user_id).await
+                        WebSocketEvent::RemoveParticipant(removed_id) => {
+                            let removed = remove_participant(
+                                store.as_ref(),
+                                poll_id.clone(),
+                                user_id.clone(),
+                                rooms.clone(),
+                                removed_id.clone(),
+                            )
+                            .await;
+                            if removed.is_ok() {
+                                tx.broadcast(WebSocketEvent::ParticipantRemoved(removed_id));
+                            }
+                            removed
                         }
 
                         WebSocketEvent::Nomination(nomination) => {
                             let nomination_id = create_nomination_id();
                             add_nomination(
-                                &mut con,
+                                store.as_ref(),
                                 poll_id.clone(),
                                 user_id.clone(),
                                 nomination_id,
-                                rooms.clone(),
                                 nomination,
                             )
                             .await
                         }
                         WebSocketEvent::RemoveNomination(nomination_id) => {
-                            rooms
-                                .remove_nomination(poll_id.clone(), nomination_id.clone())
-                                .await;
-                            polls::remove_nomination(&mut con, poll_id.clone(), nomination_id).await
+                            store
+                                .remove_nomination(poll_id.clone(), nomination_id)
+                                .await
+                        }
+                        WebSocketEvent::OpenNominations => {
+                            open_nominations(store.as_ref(), poll_id.clone(), user_id.clone()).await
                         }
                         WebSocketEvent::StartVote => {
-                            start_vote(&mut con, poll_id.clone(), user_id.clone()).await
+                            start_vote(store.as_ref(), poll_id.clone(), user_id.clone(), None).await
+                        }
+                        WebSocketEvent::StartVoteUntil(closes_at) => {
+                            start_vote(
+                                store.as_ref(),
+                                poll_id.clone(),
+                                user_id.clone(),
+                                Some(closes_at),
+                            )
+                            .await
                         }
                         WebSocketEvent::SubmitRankings(rankings) => {
                             submit_rankings(
-                                &mut con,
+                                store.as_ref(),
                                 poll_id.clone(),
                                 user_id.clone(),
-                                rooms.clone(),
                                 rankings,
                             )
                             .await
                         }
+                        WebSocketEvent::SubmitApprovals(approvals) => {
+                            submit_approvals(
+                                store.as_ref(),
+                                poll_id.clone(),
+                                user_id.clone(),
+                                approvals,
+                            )
+                            .await
+                        }
+                        WebSocketEvent::SubmitScores(scores) => {
+                            submit_scores(store.as_ref(), poll_id.clone(), user_id.clone(), scores)
+                                .await
+                        }
+                        WebSocketEvent::SubmitAllocations(allocations) => {
+                            submit_allocations(
+                                store.as_ref(),
+                                poll_id.clone(),
+                                user_id.clone(),
+                                allocations,
+                            )
+                            .await
+                        }
+
+                        WebSocketEvent::EstimateItem(item) => {
+                            estimate_item(store.as_ref(), poll_id.clone(), user_id.clone(), item)
+                                .await
+                        }
+                        WebSocketEvent::PlayCard(card) => {
+                            play_card(store.as_ref(), poll_id.clone(), user_id.clone(), card).await
+                        }
+                        WebSocketEvent::RevealCards => {
+                            reveal_cards(store.as_ref(), poll_id.clone(), user_id.clone()).await
+                        }
 
                         WebSocketEvent::ClosePoll => {
-                            close_poll(&mut con, poll_id.clone(), user_id.clone()).await
+                            close_poll(store.as_ref(), poll_id.clone(), user_id.clone(), false)
+                                .await
+                        }
+                        WebSocketEvent::ForceClosePoll => {
+                            close_poll(store.as_ref(), poll_id.clone(), user_id.clone(), true).await
+                        }
+                        WebSocketEvent::Nudge => {
+                            match nudge(store.as_ref(), poll_id.clone(), user_id.clone()).await {
+                                Ok(waiting) => {
+                                    tx.broadcast(WebSocketEvent::Reminder(waiting));
+                                    continue;
+                                }
+                                Err(err) => Err(err),
+                            }
+                        }
+                        WebSocketEvent::StartRunoff(top) => {
+                            let runoff = start_runoff(
+                                store.as_ref(),
+                                poll_id.clone(),
+                                user_id.clone(),
+                                rooms.clone(),
+                                top,
+                                ttl,
+                            )
+                            .await;
+                            match runoff {
+                                Ok(runoff) => {
+                                    tx.broadcast(WebSocketEvent::RunoffStarted(Box::new(runoff)));
+                                    continue;
+                                }
+                                Err(err) => Err(err),
+                            }
+                        }
+                        WebSocketEvent::SetRole {
+                            user_id: target_id,
+                            role,
+                        } => {
+                            set_role(
+                                store.as_ref(),
+                                poll_id.clone(),
+                                user_id.clone(),
+                                target_id,
+                                role,
+                            )
+                            .await
+                        }
+                        WebSocketEvent::TransferAdmin(target_id) => {
+                            set_role(
+                                store.as_ref(),
+                                poll_id.clone(),
+                                user_id.clone(),
+                                target_id,
+                                Role::Admin,
+                            )
+                            .await
                         }
                         WebSocketEvent::CancelPoll => {
-                            cancel_poll(&mut con, poll_id.clone(), user_id.clone()).await
+                            cancel_poll(store.as_ref(), poll_id.clone(), user_id.clone()).await
                         }
                         _ => Err(Error::UnsupportedWebsocketEvent),
                     };
                     if let Err(err) = event {
                         match err {
                             Error::PollCancelled => {
-                                let message = WebSocketEvent::PollCancelled.message();
-                                tx.broadcast(message);
+                                tx.broadcast(WebSocketEvent::PollCancelled);
 
                                 // we're completed this vote
-                                // delete room info
+                                // delete room info, and close every socket
+                                // of the room as if the poll was deleted
                                 rooms.remove(poll_id.clone()).await;
+                                let _ = notify_tx.send(poll_id.clone());
                                 break;
                             }
                             _ => {
@@ -210,9 +414,12 @@ async fn handle_socket(
                         }
                         continue;
                     }
-                    let poll = event.unwrap();
-                    let message = WebSocketEvent::PollUpdated(Box::new(poll)).message();
-                    tx.broadcast(message);
+                    let mut poll = event.unwrap();
+                    if is_ballot {
+                        tx.broadcast(WebSocketEvent::VotingProgress(poll.progress()));
+                        poll = close_if_all_voted(store.as_ref(), poll).await;
+                    }
+                    tx.broadcast(WebSocketEvent::PollUpdated(Box::new(poll)));
                 }
                 _ => {}
             }
@@ -234,88 +441,390 @@ async fn handle_socket(
             send_task.abort()
         }
     };
+
+    // however the socket closed, this connection is gone
+    let poll_id = left_poll_id.read().unwrap().clone();
+    state
+        .rooms
+        .remove_client(poll_id.clone(), left_connection_id)
+        .await;
+    if state.env.admin_timeout > 0 {
+        tokio::spawn(promote_if_gone(
+            leaver_store,
+            state.rooms.clone(),
+            poll_id,
+            leaver_id,
+            Duration::from_secs(state.env.admin_timeout),
+        ));
+    }
 }
 
-async fn cancel_poll<C>(con: &mut C, poll_id: String, user_id: String) -> Result<Poll, Error>
-where
-    C: ConnectionLike,
-{
-    let poll = polls::get_poll(con, poll_id.clone()).await?;
-    if poll.admin_id != user_id {
-        return Err(Error::AdminPrivilegesRequired);
+/// If `user_id` is the admin and has no connection open after `timeout`, makes
+/// the moderator who joined first the admin, or if none is connected, the
+/// participant who did.
+async fn promote_if_gone(
+    store: DynPollStore,
+    rooms: Arc<Rooms>,
+    poll_id: String,
+    user_id: String,
+    timeout: Duration,
+) {
+    match store.get_poll(poll_id.clone()).await {
+        Ok(poll) if poll.admin_id == user_id => {}
+        _ => return,
+    }
+    tokio::time::sleep(timeout).await;
+
+    let Ok(poll) = store.get_poll(poll_id.clone()).await else {
+        return;
+    };
+    let mut clients: Vec<RoomClient> = rooms
+        .clients(poll_id.clone())
+        .await
+        .into_iter()
+        .filter(|client| !client.spectator)
+        .collect();
+    if poll.admin_id != user_id || clients.iter().any(|client| client.id == user_id) {
+        return;
     }
-    polls::del_poll(con, poll_id.clone()).await?;
+    clients.sort_by_key(|client| (poll.role(&client.id) != Role::Moderator, client.join_time));
+    let Some(successor) = clients.first() else {
+        return;
+    };
+    if let Ok(poll) = store
+        .transfer_admin(poll_id.clone(), successor.id.clone())
+        .await
+    {
+        rooms
+            .broadcast(poll_id, WebSocketEvent::PollUpdated(Box::new(poll)))
+            .await;
+    }
+}
+
+/// Adds the participant to the poll. Once it is over, nobody new can join,
+/// but those who took part can still read it, to see the results.
+pub async fn reconnect(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    name: String,
+) -> Result<Poll, Error> {
+    match store
+        .add_participant(poll_id.clone(), user_id.clone(), name)
+        .await
+    {
+        Err(Error::InvalidPhase) => {
+            let poll = store.get_poll(poll_id).await?;
+            match poll.participants.contains_key(&user_id) {
+                true => Ok(poll),
+                false => Err(Error::InvalidPhase),
+            }
+        }
+        res => res,
+    }
+}
+
+/// The poll, if `user_id` has `role` or a higher one in it.
+async fn authorize(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: &str,
+    role: Role,
+) -> Result<Poll, Error> {
+    let poll = store.get_poll(poll_id).await?;
+    poll.require(user_id, role)?;
+    Ok(poll)
+}
+
+/// Changes the role of a participant, handing over the admin role when
+/// `role` is `Role::Admin`.
+async fn set_role(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    target_id: String,
+    role: Role,
+) -> Result<Poll, Error> {
+    let poll = authorize(store, poll_id.clone(), &user_id, Role::Admin).await?;
+    match role {
+        Role::Admin => store.transfer_admin(poll_id, target_id).await,
+        _ if poll.admin_id == target_id => Err(Error::CannotDemoteAdmin),
+        _ => {
+            store
+                .set_moderator(poll_id, target_id, role == Role::Moderator)
+                .await
+        }
+    }
+}
+
+async fn cancel_poll(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+) -> Result<Poll, Error> {
+    authorize(store, poll_id.clone(), &user_id, Role::Admin).await?;
+    store.cancel_poll(poll_id).await?;
     Err(Error::PollCancelled)
 }
 
-async fn close_poll<C>(con: &mut C, poll_id: String, user_id: String) -> Result<Poll, Error>
-where
-    C: ConnectionLike,
-{
-    let poll = polls::get_poll(con, poll_id.clone()).await?;
-    if poll.admin_id != user_id {
-        return Err(Error::AdminPrivilegesRequired);
+async fn close_poll(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    force: bool,
+) -> Result<Poll, Error> {
+    let poll = authorize(store, poll_id, &user_id, Role::Admin).await?;
+    close(store, poll, force).await
+}
+
+/// Tallies `poll` and closes it, whether the admin closes it or one of its
+/// close policies does.
+///
+/// Fails with `Error::NoQuorum` below quorum, unless `force`, which closes
+/// it marked `Poll.no_quorum`.
+pub async fn close(store: &dyn PollStore, poll: Poll, force: bool) -> Result<Poll, Error> {
+    let no_quorum = match poll.check_quorum() {
+        Ok(()) => false,
+        Err(_) if force => true,
+        Err(err) => return Err(err),
+    };
+    let (results, report) = poll.get_results();
+    store.add_results(poll.id, results, report, no_quorum).await
+}
+
+/// Closes a poll set to close itself once every participant voted, and
+/// returns it as it is after the last ballot, or after the last participant
+/// who had not voted left.
+pub async fn close_if_all_voted(store: &dyn PollStore, poll: Poll) -> Poll {
+    let progress = poll.progress();
+    if !poll.close_when_all_voted
+        || poll.phase != PollPhase::Voting
+        || progress.total == 0
+        || !progress.waiting.is_empty()
+    {
+        return poll;
+    }
+    // someone else may have closed it in between
+    close(store, poll.clone(), false).await.unwrap_or(poll)
+}
+
+/// Bans a participant, whose socket is then closed, and closes the poll if
+/// everyone left voted.
+pub(crate) async fn remove_participant(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    rooms: Arc<Rooms>,
+    removed_id: String,
+) -> Result<Poll, Error> {
+    let poll = authorize(store, poll_id.clone(), &user_id, Role::Admin).await?;
+    if poll.admin_id == removed_id {
+        return Err(Error::CannotRemoveAdmin);
     }
-    let results = poll.get_results();
-    polls::add_results(con, poll_id.clone(), results).await
+    let poll = store
+        .ban_participant(poll_id.clone(), removed_id.clone())
+        .await?;
+    rooms.remove_user(poll_id, removed_id).await;
+    Ok(close_if_all_voted(store, poll).await)
 }
 
-async fn start_vote<C>(con: &mut C, poll_id: String, user_id: String) -> Result<Poll, Error>
-where
-    C: ConnectionLike,
-{
-    let poll = polls::get_poll(con, poll_id.clone()).await?;
-    if poll.admin_id != user_id {
-        return Err(Error::AdminPrivilegesRequired);
+/// Starts the runoff of a closed poll and moves its room to it.
+async fn start_runoff(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    rooms: Arc<Rooms>,
+    top: usize,
+    ttl: usize,
+) -> Result<Poll, Error> {
+    let poll = authorize(store, poll_id.clone(), &user_id, Role::Admin).await?;
+    let runoff = store
+        .add_poll(ttl, poll.runoff(create_poll_id(), top)?)
+        .await?;
+    // claims the runoff, so only one is ever linked; a runoff that lost the
+    // claim is deleted again
+    if let Err(err) = store.set_runoff(poll_id.clone(), runoff.id.clone()).await {
+        store.del_poll(runoff.id).await?;
+        return Err(err);
     }
+
+    rooms.move_room(poll_id, runoff.id.clone()).await;
+    Ok(runoff)
+}
+
+/// Participants who have not voted yet, to remind them.
+async fn nudge(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+) -> Result<Vec<UserID>, Error> {
+    let poll = authorize(store, poll_id, &user_id, Role::Moderator).await?;
+    if poll.phase != PollPhase::Voting {
+        return Err(Error::InvalidPhase);
+    }
+    Ok(poll.progress().waiting)
+}
+
+async fn open_nominations(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+) -> Result<Poll, Error> {
+    authorize(store, poll_id.clone(), &user_id, Role::Moderator).await?;
+    store.open_nominations(poll_id).await
+}
+
+async fn start_vote(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    closes_at: Option<i64>,
+) -> Result<Poll, Error> {
+    let poll = authorize(store, poll_id.clone(), &user_id, Role::Moderator).await?;
     if poll.nominations.is_empty() {
         return Err(Error::NoNomination);
     }
-    polls::start_poll(con, poll_id.clone()).await
+    if closes_at.is_some_and(|closes_at| closes_at <= chrono::Utc::now().timestamp_millis()) {
+        return Err(Error::DeadlineInPast);
+    }
+    store.start_poll(poll_id.clone(), closes_at).await
 }
 
-async fn add_nomination<C>(
-    con: &mut C,
+async fn add_nomination(
+    store: &dyn PollStore,
     poll_id: String,
     user_id: String,
     nomination_id: String,
-    rooms: Arc<Rooms>,
     nomination: AddNominationReq,
-) -> Result<Poll, Error>
-where
-    C: ConnectionLike,
-{
+) -> Result<Poll, Error> {
     if let Err(err) = nomination.validate() {
         return Err(Error::ValidationError(err));
     }
-    rooms
-        .add_nomination(poll_id.clone(), nomination_id.clone())
-        .await;
+    // only moderators post the items of an estimation poll
+    let poll = store.get_poll(poll_id.clone()).await?;
+    if poll.kind == PollKind::Estimation {
+        poll.require(&user_id, Role::Moderator)?;
+    }
     let nomination = Nomination {
         text: nomination.text,
         user_id,
+        created_at: chrono::Utc::now().timestamp_millis(),
     };
 
-    polls::add_nomination(con, poll_id, nomination_id, nomination).await
+    store
+        .add_nomination(poll_id, nomination_id, nomination)
+        .await
 }
 
-async fn submit_rankings<C>(
-    con: &mut C,
+async fn submit_rankings(
+    store: &dyn PollStore,
     poll_id: String,
     user_id: String,
-    rooms: Arc<Rooms>,
     rankings: RankingList,
-) -> Result<Poll, Error>
-where
-    C: ConnectionLike,
-{
-    if !rooms
-        .contains_nomination(poll_id.clone(), rankings.clone())
+) -> Result<Poll, Error> {
+    store
+        .get_poll(poll_id.clone())
+        .await?
+        .check_rankings(&rankings)?;
+    store
+        .add_participant_rankings(poll_id, user_id, rankings)
         .await
-    {
-        return Err(Error::UnknownNomination);
+}
+
+pub(crate) async fn submit_approvals(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    approvals: ApprovalList,
+) -> Result<Poll, Error> {
+    if approvals.is_empty() {
+        return Err(Error::EmptyBallot);
+    }
+    store
+        .get_poll(poll_id.clone())
+        .await?
+        .check_nominations(&approvals)?;
+    store
+        .add_participant_approvals(poll_id, user_id, approvals)
+        .await
+}
+
+pub(crate) async fn submit_scores(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    scores: ScoreList,
+) -> Result<Poll, Error> {
+    if scores.is_empty() {
+        return Err(Error::EmptyBallot);
+    }
+    if scores.values().any(|score| *score > MAX_SCORE) {
+        return Err(Error::ScoreOutOfRange);
+    }
+    store
+        .get_poll(poll_id.clone())
+        .await?
+        .check_nominations(scores.keys())?;
+    store.add_participant_scores(poll_id, user_id, scores).await
+}
+
+pub(crate) async fn submit_allocations(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    allocations: AllocationList,
+) -> Result<Poll, Error> {
+    if allocations.values().all(|votes| *votes == 0) {
+        return Err(Error::EmptyBallot);
+    }
+    // the budget is fixed when the poll is created
+    let poll = store.get_poll(poll_id.clone()).await?;
+    poll.check_nominations(allocations.keys())?;
+    let cost = poll.credit_cost.ballot_cost(&allocations);
+    if cost > poll.credit_budget {
+        return Err(Error::OverBudget {
+            cost,
+            budget: poll.credit_budget,
+        });
+    }
+    store
+        .add_participant_allocations(poll_id, user_id, allocations)
+        .await
+}
+
+async fn estimate_item(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    item: NominationID,
+) -> Result<Poll, Error> {
+    authorize(store, poll_id.clone(), &user_id, Role::Moderator)
+        .await?
+        .check_nominations([&item])?;
+    store.start_estimate(poll_id, item).await
+}
+
+async fn play_card(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+    card: String,
+) -> Result<Poll, Error> {
+    // the deck is fixed when the poll is created
+    let poll = store.get_poll(poll_id.clone()).await?;
+    if !poll.deck.cards().contains(&card.as_str()) {
+        return Err(Error::UnknownCard);
     }
-    polls::add_participant_rankings(con, poll_id, user_id, rankings).await
+    store.add_card(poll_id, user_id, card).await
+}
+
+async fn reveal_cards(
+    store: &dyn PollStore,
+    poll_id: String,
+    user_id: String,
+) -> Result<Poll, Error> {
+    authorize(store, poll_id.clone(), &user_id, Role::Moderator).await?;
+    store.reveal_cards(poll_id).await
 }
 
 async fn send_message(
@@ -3,6 +3,7 @@ pub mod data;
 pub mod errors;
 pub mod handlers;
 pub mod models;
+pub mod scoring;
 pub mod services;
 pub mod shared;
 pub mod state;
@@ -0,0 +1,72 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::{NominationID, UserID};
+
+/// Card played when a participant can not estimate.
+pub const UNSURE_CARD: &str = "?";
+
+/// Cards participants pick from in an estimation poll.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Deck {
+    #[default]
+    Fibonacci,
+    TShirt,
+}
+
+impl Deck {
+    /// Every card of the deck, ending with `UNSURE_CARD`.
+    pub fn cards(&self) -> &'static [&'static str] {
+        match self {
+            Deck::Fibonacci => &[
+                "0", "1", "2", "3", "5", "8", "13", "21", "34", "55", "89", "?",
+            ],
+            Deck::TShirt => &["XS", "S", "M", "L", "XL", "XXL", "?"],
+        }
+    }
+
+    /// Position of `card` in the deck, `None` for the unsure card and cards
+    /// not in the deck.
+    pub fn position(&self, card: &str) -> Option<usize> {
+        if card == UNSURE_CARD {
+            return None;
+        }
+        self.cards().iter().position(|c| *c == card)
+    }
+
+    /// What `card` is worth when averaging: its number, or its 1 based
+    /// position for decks that are not numbers.
+    pub fn value(&self, card: &str) -> Option<f64> {
+        let position = self.position(card)?;
+        match self {
+            Deck::Fibonacci => card.parse().ok(),
+            Deck::TShirt => Some((position + 1) as f64),
+        }
+    }
+}
+
+/// The item being estimated and the cards played on it so far.
+///
+/// Starting a new item resets the cards.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Estimation {
+    pub item: Option<NominationID>,
+    /// Hidden from participants until `revealed`, see `Poll::view_for`.
+    pub cards: HashMap<UserID, String>,
+    pub revealed: bool,
+}
+
+/// How the cards of a revealed item spread.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EstimateSummary {
+    /// Number of times each card was played.
+    pub spread: BTreeMap<String, usize>,
+    /// Average value of the cards played, unsure cards left out.
+    pub average: Option<f64>,
+    /// Card of the deck closest to the average.
+    pub estimate: Option<String>,
+    /// Participants more than one card away from the median card.
+    pub outliers: Vec<UserID>,
+}
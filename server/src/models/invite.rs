@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+pub type InviteID = String;
+
+/// An invite link to a poll, as the store tracks it. The link itself is a
+/// token signed by `auth::invite_token_gen`, which carries the expiry.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Invite {
+    pub max_uses: usize,
+    pub uses: usize,
+    /// Name every user joining with the invite gets, if set.
+    pub name: Option<String>,
+    /// Unix timestamp in seconds, same as the token's.
+    pub expires_at: i64,
+    pub revoked: bool,
+}
+
+// =============================================================================
+// DTO object
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AddInviteReq {
+    #[validate(range(min = 1, max = 1000))]
+    pub max_uses: usize,
+    /// Seconds the invite can be used for.
+    #[validate(range(min = 60, max = 604800))]
+    pub expires_in: usize,
+    #[validate(length(min = 1, max = 25, message = "Can not be empty"))]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddInviteResp {
+    pub invite_id: InviteID,
+    pub invite_token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RevokeInviteReq {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    pub invite_id: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RedeemInviteReq {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    pub invite_token: String,
+    /// Ignored if the invite sets a name, required otherwise.
+    #[validate(length(min = 1, max = 25, message = "Can not be empty"))]
+    pub name: Option<String>,
+    /// Required by polls created with a passphrase, an invite does not
+    /// replace it.
+    #[validate(length(max = 128))]
+    pub passphrase: Option<String>,
+}
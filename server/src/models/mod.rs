@@ -6,3 +6,11 @@ pub use ws::*;
 pub mod room;
 mod sse;
 pub use sse::*;
+mod estimation;
+pub use estimation::*;
+mod phase;
+pub use phase::*;
+mod role;
+pub use role::*;
+mod invite;
+pub use invite::*;
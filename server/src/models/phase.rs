@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+
+/// Where a poll is in its lifecycle.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PollPhase {
+    /// Participants are joining.
+    #[default]
+    Lobby,
+    Nominating,
+    Voting,
+    Closed,
+    Cancelled,
+}
+
+/// Something done to a poll, allowed in some phases only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollAction {
+    Join,
+    Leave,
+    /// Adding a nomination. The first one opens nominations if no
+    /// moderator has yet.
+    Nominate,
+    RemoveNomination,
+    OpenNominations,
+    StartVoting,
+    /// Dropping the deadline of a poll being voted on.
+    ClearDeadline,
+    /// Submitting a ballot, or anything done while estimating items.
+    Vote,
+    Close,
+    Cancel,
+    /// Linking the runoff started after the poll closed.
+    Runoff,
+    /// Removing a participant for good.
+    Ban,
+    /// Changing the role of a participant.
+    SetRole,
+    /// Handing out, using or revoking an invite.
+    Invite,
+}
+
+impl PollAction {
+    /// The transition table: phases the action is allowed in, and the phase
+    /// it moves the poll to, if any.
+    pub fn transition(&self) -> (&'static [PollPhase], Option<PollPhase>) {
+        use PollPhase::*;
+
+        match self {
+            PollAction::Join => (&[Lobby, Nominating, Voting], None),
+            PollAction::Leave => (&[Lobby, Nominating], None),
+            PollAction::Nominate => (&[Lobby, Nominating], Some(Nominating)),
+            PollAction::RemoveNomination => (&[Nominating], None),
+            PollAction::OpenNominations => (&[Lobby], Some(Nominating)),
+            PollAction::StartVoting => (&[Nominating], Some(Voting)),
+            PollAction::ClearDeadline => (&[Voting], None),
+            PollAction::Vote => (&[Voting], None),
+            PollAction::Close => (&[Voting], Some(Closed)),
+            PollAction::Cancel => (&[Lobby, Nominating, Voting], Some(Cancelled)),
+            PollAction::Runoff => (&[Closed], None),
+            PollAction::Ban => (&[Lobby, Nominating, Voting], None),
+            PollAction::SetRole => (&[Lobby, Nominating, Voting, Closed], None),
+            PollAction::Invite => (&[Lobby, Nominating, Voting], None),
+        }
+    }
+}
+
+impl PollPhase {
+    /// Moves to the phase `action` leads to, failing with
+    /// `Error::InvalidPhase` if it is not allowed in this one.
+    pub fn apply(&mut self, action: PollAction) -> Result<(), Error> {
+        let (allowed, next) = action.transition();
+        if !allowed.contains(self) {
+            return Err(Error::InvalidPhase);
+        }
+        if let Some(next) = next {
+            *self = next;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_should_follow_transitions() {
+        let mut phase = PollPhase::default();
+        phase.apply(PollAction::Join).unwrap();
+        assert_eq!(PollPhase::Lobby, phase);
+
+        phase.apply(PollAction::OpenNominations).unwrap();
+        phase.apply(PollAction::StartVoting).unwrap();
+        phase.apply(PollAction::Close).unwrap();
+        assert_eq!(PollPhase::Closed, phase);
+    }
+
+    #[test]
+    fn test_apply_should_reject_invalid_phase() {
+        let mut phase = PollPhase::Lobby;
+        assert_eq!(Err(Error::InvalidPhase), phase.apply(PollAction::Close));
+        assert_eq!(PollPhase::Lobby, phase);
+
+        let mut phase = PollPhase::Closed;
+        assert_eq!(Err(Error::InvalidPhase), phase.apply(PollAction::Join));
+        assert_eq!(Err(Error::InvalidPhase), phase.apply(PollAction::Cancel));
+    }
+
+    #[test]
+    fn test_apply_should_open_nominations_on_first_nomination() {
+        let mut phase = PollPhase::Lobby;
+        assert_eq!(
+            Err(Error::InvalidPhase),
+            phase.apply(PollAction::RemoveNomination)
+        );
+
+        phase.apply(PollAction::Nominate).unwrap();
+        assert_eq!(PollPhase::Nominating, phase);
+        phase.apply(PollAction::Nominate).unwrap();
+        phase.apply(PollAction::RemoveNomination).unwrap();
+        assert_eq!(PollPhase::Nominating, phase);
+
+        let mut phase = PollPhase::Voting;
+        assert_eq!(Err(Error::InvalidPhase), phase.apply(PollAction::Nominate));
+    }
+}
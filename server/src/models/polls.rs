@@ -1,9 +1,20 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap, HashSet},
+};
 
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
-use crate::errors::Error;
+use crate::{
+    errors::Error,
+    models::{Deck, EstimateSummary, Estimation, Invite, InviteID, PollPhase, Role},
+    scoring::{
+        approval, cumulative, draw, instant_runoff, rank, schulze, seed_as_string,
+        single_transferable_vote, star, CreditCost, Placing, ScoringMethodKind, TallyReport,
+        TieBreak, TieOrder, MAX_SCORE,
+    },
+};
 
 pub type NominationID = String;
 
@@ -13,6 +24,9 @@ pub type Participants = HashMap<String, String>;
 pub struct Nomination {
     pub text: String,
     pub user_id: String,
+    /// Unix timestamp in milliseconds.
+    #[serde(default)]
+    pub created_at: i64,
 }
 
 pub type Nominations = HashMap<NominationID, Nomination>;
@@ -20,27 +34,187 @@ pub type Nominations = HashMap<NominationID, Nomination>;
 pub type UserID = String;
 pub type Rankings = HashMap<UserID, RankingList>;
 pub type RankingList = Vec<NominationID>;
+pub type Approvals = HashMap<UserID, ApprovalList>;
+pub type ApprovalList = Vec<NominationID>;
+pub type Scores = HashMap<UserID, ScoreList>;
+pub type ScoreList = HashMap<NominationID, u8>;
+pub type Allocations = HashMap<UserID, AllocationList>;
+pub type AllocationList = HashMap<NominationID, u32>;
 
-#[derive(Debug, Serialize, Clone, Deserialize, PartialEq, Eq)]
+/// What a poll is run for.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PollKind {
+    /// Participants vote on the nominations with the poll's ballot type.
+    #[default]
+    Ranking,
+    /// Planning poker: the admin posts items and participants estimate them
+    /// one at a time with cards from `Poll.deck`.
+    Estimation,
+}
+
+/// Shape of the ballots a poll takes.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BallotType {
+    /// An ordered list of nominations, stored in `Poll.rankings`.
+    #[default]
+    Ranked,
+    /// Every acceptable nomination, stored in `Poll.approvals`.
+    Approval,
+    /// A 0 to 5 score per nomination, stored in `Poll.scores`, tallied with
+    /// STAR.
+    Score,
+    /// Votes per nomination paid for out of `Poll.credit_budget`, stored in
+    /// `Poll.allocations`.
+    Cumulative,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, PartialEq)]
 pub struct Result {
     pub nomination_id: NominationID,
     pub nomination_text: String,
-    pub score: String,
+    /// Points, votes or last round tally, depending on the tally. Average
+    /// card value for estimation polls.
+    pub score: f64,
+    /// 1 based, shared by tied nominations.
+    #[serde(default)]
+    pub rank: usize,
+    #[serde(default)]
+    pub tied: bool,
+    /// Ballots placing the nomination at each position, 0 based, one entry
+    /// per vote a voter has. For score and cumulative ballots, ballots giving
+    /// it each score or number of votes instead, from 0 to the most a ballot
+    /// can give. For approval ballots, ballots approving it along with 0 to
+    /// all the other nominations.
+    #[serde(default)]
+    pub histogram: Vec<usize>,
+    /// Ballots placing the nomination at all.
+    #[serde(default)]
+    pub ballots: usize,
+    /// Score over the total score of all the results, from 0 to 1. None for
+    /// Schulze and single transferable vote, whose scores do not add up.
+    #[serde(default)]
+    pub share: Option<f64>,
 }
 
 pub type Results = Vec<Result>;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+/// How many participants must vote for the results to count.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Quorum {
+    Voters(usize),
+    /// Percentage of the participants, rounded up.
+    Percent(usize),
+}
+
+/// Voting progress, sent to the room after every ballot.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VotingProgress {
+    pub voted: Vec<UserID>,
+    /// Participants who have not voted yet.
+    pub waiting: Vec<UserID>,
+    pub voted_count: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct Poll {
     pub id: String,
     pub topic: String,
     pub votes_per_voter: usize,
     pub participants: Participants,
     pub admin_id: String,
+    #[serde(default)]
+    pub moderators: BTreeSet<UserID>,
     pub nominations: Nominations,
     pub rankings: Rankings,
+    #[serde(default)]
+    pub approvals: Approvals,
+    #[serde(default)]
+    pub scores: Scores,
+    #[serde(default)]
+    pub allocations: Allocations,
     pub results: Results,
-    pub has_started: bool,
+    #[serde(default)]
+    pub phase: PollPhase,
+    #[serde(default)]
+    pub kind: PollKind,
+    #[serde(default)]
+    pub ballot_type: BallotType,
+    /// Only used by ranked ballots.
+    #[serde(default)]
+    pub scoring_method: ScoringMethodKind,
+    /// Number of nominations every ranked ballot must rank, if set.
+    #[serde(default)]
+    pub rank_exactly: Option<usize>,
+    /// Number of winners, used by `ScoringMethodKind::SingleTransferableVote`.
+    #[serde(default = "default_seats")]
+    pub seats: usize,
+    /// Credits every voter can spend, used by cumulative ballots.
+    #[serde(default = "default_credit_budget")]
+    pub credit_budget: usize,
+    #[serde(default)]
+    pub credit_cost: CreditCost,
+    #[serde(default)]
+    pub report: Option<TallyReport>,
+    #[serde(default)]
+    pub tie_break: TieBreak,
+    /// Seed of the draw of `TieBreak::Random`, hidden until the poll closes.
+    #[serde(default, with = "seed_as_string")]
+    pub tie_break_seed: Option<u64>,
+    /// Only used by estimation polls.
+    #[serde(default)]
+    pub deck: Deck,
+    #[serde(default)]
+    pub estimation: Estimation,
+    /// Summary of the last reveal of every estimated item.
+    #[serde(default)]
+    pub estimates: HashMap<NominationID, EstimateSummary>,
+    /// Unix timestamp in milliseconds before which the poll can not be
+    /// joined.
+    #[serde(default)]
+    pub open_at: Option<i64>,
+    /// Unix timestamp in milliseconds voting starts at, see
+    /// `handlers::schedule`.
+    #[serde(default)]
+    pub voting_starts_at: Option<i64>,
+    #[serde(default)]
+    pub quorum: Option<Quorum>,
+    /// Closed below quorum, so the results do not count.
+    #[serde(default)]
+    pub no_quorum: bool,
+    /// Closes the poll once every participant has voted.
+    #[serde(default)]
+    pub close_when_all_voted: bool,
+    /// Unix timestamp in milliseconds the poll closes at, set when voting
+    /// starts. Dropped if quorum is not met by then.
+    #[serde(default)]
+    pub closes_at: Option<i64>,
+    /// Participants removed by the admin, who can not join again.
+    #[serde(default)]
+    pub banned: BTreeSet<UserID>,
+    /// Poll this one is a runoff of, see `Poll::runoff`.
+    #[serde(default)]
+    pub runoff_of: Option<String>,
+    /// Runoff started after this poll closed.
+    #[serde(default)]
+    pub runoff_id: Option<String>,
+    /// Participants only see their own ballot, see `Poll::view_for`.
+    #[serde(default)]
+    pub secret_ballots: bool,
+    /// Argon2 hash of the passphrase needed to join, see
+    /// `shared::passphrase`. Never sent to clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase_hash: Option<String>,
+    /// Invite links handed out by the admin, only shown to them.
+    #[serde(default)]
+    pub invites: HashMap<InviteID, Invite>,
+    /// Participants who have submitted a ballot. Filled in by
+    /// `Poll::view_for`, and left empty in the stored poll.
+    #[serde(default)]
+    pub voted: BTreeSet<UserID>,
 }
 
 impl Poll {
@@ -50,6 +224,8 @@ impl Poll {
             topic,
             votes_per_voter,
             admin_id: user_id,
+            seats: default_seats(),
+            credit_budget: default_credit_budget(),
             ..Default::default()
         }
     }
@@ -58,46 +234,546 @@ impl Poll {
         serde_json::to_string(self).unwrap()
     }
 
-    pub fn get_results(&self) -> Results {
-        // 1. Each value of `rankings` key values is an array of a participants'
-        // vote. Points for each array element corresponds to following formula:
-        // r_n = ((votes_per_voter - 0.5*n) / votes_per_voter)^(n+1), where n corresponds
-        // to array index of rankings.
-        // Accumulate score per NominationID
-        let mut scores: HashMap<NominationID, f64> = HashMap::new();
-
-        let votes_per_voter = self.votes_per_voter as f64;
-        for pre_rankings in self.rankings.values() {
-            for (n, nomination_id) in pre_rankings.iter().enumerate() {
-                let vote_value =
-                    ((votes_per_voter - 0.5 * n as f64) / votes_per_voter).powf(n as f64 + 1.0);
-
-                *scores.entry(nomination_id.clone()).or_insert(0.0) += vote_value;
+    /// Participants who have submitted a ballot, of any type.
+    fn voters(&self) -> BTreeSet<UserID> {
+        self.rankings
+            .keys()
+            .chain(self.approvals.keys())
+            .chain(self.scores.keys())
+            .chain(self.allocations.keys())
+            .cloned()
+            .collect()
+    }
+
+    pub fn role(&self, user_id: &str) -> Role {
+        if self.admin_id == user_id {
+            Role::Admin
+        } else if self.moderators.contains(user_id) {
+            Role::Moderator
+        } else {
+            Role::Voter
+        }
+    }
+
+    /// Fails unless `user_id` has `role` or a higher one.
+    pub fn require(&self, user_id: &str, role: Role) -> std::result::Result<(), Error> {
+        match role {
+            _ if self.role(user_id) >= role => Ok(()),
+            Role::Admin => Err(Error::AdminPrivilegesRequired),
+            _ => Err(Error::ModeratorPrivilegesRequired),
+        }
+    }
+
+    /// A follow-up round between the nominations placed in the top `top` of
+    /// the results, with ties at the cut all kept. It has the same
+    /// participants, admin and settings, and starts in
+    /// `PollPhase::Voting`.
+    pub fn runoff(&self, runoff_id: String, top: usize) -> std::result::Result<Poll, Error> {
+        if self.kind != PollKind::Ranking {
+            return Err(Error::WrongPollKind);
+        }
+        let nominations: Nominations = self
+            .results
+            .iter()
+            .filter(|result| result.rank <= top)
+            .filter_map(|result| {
+                let nomination = self.nominations.get(&result.nomination_id)?;
+                Some((result.nomination_id.clone(), nomination.clone()))
+            })
+            .collect();
+        if nominations.len() < 2 {
+            return Err(Error::RunoffTooSmall);
+        }
+
+        let votes_per_voter = self.votes_per_voter.min(nominations.len());
+        Ok(Poll {
+            participants: self.participants.clone(),
+            nominations,
+            phase: PollPhase::Voting,
+            kind: self.kind,
+            ballot_type: self.ballot_type,
+            scoring_method: self.scoring_method,
+            rank_exactly: self.rank_exactly,
+            seats: self.seats,
+            credit_budget: self.credit_budget,
+            credit_cost: self.credit_cost,
+            tie_break: self.tie_break,
+            tie_break_seed: self.tie_break_seed.map(|_| rand::random()),
+            quorum: self.quorum,
+            close_when_all_voted: self.close_when_all_voted,
+            secret_ballots: self.secret_ballots,
+            passphrase_hash: self.passphrase_hash.clone(),
+            moderators: self.moderators.clone(),
+            banned: self.banned.clone(),
+            runoff_of: Some(self.id.clone()),
+            ..Poll::new(
+                runoff_id,
+                self.topic.clone(),
+                votes_per_voter,
+                self.admin_id.clone(),
+            )
+        })
+    }
+
+    /// Number of participants who must vote for the results to count.
+    pub fn quorum_required(&self) -> usize {
+        match self.quorum {
+            None => 0,
+            Some(Quorum::Voters(voters)) => voters,
+            Some(Quorum::Percent(percent)) => (percent * self.participants.len()).div_ceil(100),
+        }
+    }
+
+    /// Fails with `Error::NoQuorum` if fewer participants voted than
+    /// `quorum_required`.
+    pub fn check_quorum(&self) -> std::result::Result<(), Error> {
+        let voted = self.voters().len();
+        let required = self.quorum_required();
+        if voted < required {
+            return Err(Error::NoQuorum { voted, required });
+        }
+        Ok(())
+    }
+
+    /// Who has voted and who has not, without what they voted for.
+    pub fn progress(&self) -> VotingProgress {
+        let voted = self.voters();
+        let mut waiting: Vec<UserID> = self
+            .participants
+            .keys()
+            .filter(|id| !voted.contains(*id))
+            .cloned()
+            .collect();
+        waiting.sort();
+        VotingProgress {
+            voted_count: voted.len(),
+            total: voted.len() + waiting.len(),
+            voted: voted.into_iter().collect(),
+            waiting,
+        }
+    }
+
+    /// Fails with `Error::UnknownNomination` unless every id is one of the
+    /// poll's nominations.
+    pub fn check_nominations<'a, I>(&self, ids: I) -> std::result::Result<(), Error>
+    where
+        I: IntoIterator<Item = &'a NominationID>,
+    {
+        match ids.into_iter().all(|id| self.nominations.contains_key(id)) {
+            true => Ok(()),
+            false => Err(Error::UnknownNomination),
+        }
+    }
+
+    /// Checks a ranked ballot is well formed: not empty, only the poll's
+    /// nominations and none twice, at most `votes_per_voter` nominations,
+    /// and exactly `rank_exactly` if set.
+    pub fn check_rankings(&self, rankings: &RankingList) -> std::result::Result<(), Error> {
+        if rankings.is_empty() {
+            return Err(Error::EmptyBallot);
+        }
+        self.check_nominations(rankings)?;
+        let unique: HashSet<&NominationID> = rankings.iter().collect();
+        if unique.len() != rankings.len() {
+            return Err(Error::DuplicateRanking);
+        }
+        let max = self.votes_per_voter.min(self.nominations.len());
+        if rankings.len() > max {
+            return Err(Error::TooManyRankings { max });
+        }
+        if let Some(expected) = self.rank_exactly {
+            // can not rank more nominations than there are
+            let expected = expected.min(max);
+            if rankings.len() != expected {
+                return Err(Error::WrongRankingCount { expected });
+            }
+        }
+        Ok(())
+    }
+
+    /// The poll as `user_id` may see it.
+    ///
+    /// Other users' cards are empty strings until they are revealed. The tie
+    /// break seed is hidden until the poll closes. With secret ballots, only
+    /// the user's own ballot is shown. The passphrase hash is never shown,
+    /// and invites are shown only to the admin.
+    pub fn view_for(mut self, user_id: &str) -> Self {
+        self.voted = self.voters();
+        self.passphrase_hash = None;
+        if self.role(user_id) != Role::Admin {
+            self.invites.clear();
+        }
+        if self.secret_ballots {
+            self.rankings.retain(|id, _| id == user_id);
+            self.approvals.retain(|id, _| id == user_id);
+            self.scores.retain(|id, _| id == user_id);
+            self.allocations.retain(|id, _| id == user_id);
+        }
+        if !self.estimation.revealed {
+            for (id, card) in self.estimation.cards.iter_mut() {
+                if id != user_id {
+                    card.clear();
+                }
             }
         }
+        if self.phase != PollPhase::Closed {
+            self.tie_break_seed = None;
+        }
+        self
+    }
+
+    pub fn get_results(&self) -> (Results, Option<TallyReport>) {
+        if self.kind == PollKind::Estimation {
+            return self.get_estimation_results();
+        }
+        match self.ballot_type {
+            BallotType::Approval => return self.get_approval_results(),
+            BallotType::Score => return self.get_star_results(),
+            BallotType::Cumulative => return self.get_cumulative_results(),
+            BallotType::Ranked => {}
+        }
 
-        // 2. Take NominationID to score mapping, and merge in nomination_text
-        // and NominationID into value
-        let mut results: Results = scores
+        let Some(method) = self.scoring_method.method() else {
+            return match self.scoring_method {
+                ScoringMethodKind::Schulze => self.get_schulze_results(),
+                ScoringMethodKind::SingleTransferableVote => self.get_stv_results(),
+                _ => self.get_runoff_results(),
+            };
+        };
+
+        // 1. Each value of `rankings` key values is an array of a participants'
+        // vote. Count the positions each nomination was ranked at
+        let breakdowns = self.breakdowns();
+
+        // 2. Points for each position are given by the poll's scoring method.
+        // Adding them up position by position, rather than ballot by ballot,
+        // gives the same score to the same positions whatever order the
+        // ballots come in
+        let scores = breakdowns
+            .iter()
+            .map(|(nomination_id, breakdown)| {
+                let score = breakdown
+                    .histogram
+                    .iter()
+                    .enumerate()
+                    .map(|(n, count)| *count as f64 * method.points(n, self.votes_per_voter))
+                    .sum();
+                (nomination_id.clone(), score)
+            })
+            .collect();
+
+        // 3. Sort by score in descending order, breaking ties with the poll's
+        // policy, and merge in nomination_text
+        let results = self.ranked_results(scores, &breakdowns);
+        (results, None)
+    }
+
+    /// Results from a standing ordered by score alone.
+    fn ranked_results(
+        &self,
+        standing: Vec<(NominationID, f64)>,
+        breakdowns: &Breakdowns,
+    ) -> Results {
+        let placings = match self.tie_break {
+            TieBreak::KeepTied => rank(standing, |_| 0),
+            TieBreak::FirstPlaces => {
+                let first_places = self.first_places();
+                rank(standing, |id| {
+                    Reverse(first_places.get(id).copied().unwrap_or(0))
+                })
+            }
+            TieBreak::EarliestNomination => rank(standing, |id| {
+                self.nominations
+                    .get(id)
+                    .map(|nomination| nomination.created_at)
+                    .unwrap_or(i64::MAX)
+            }),
+            TieBreak::Random => {
+                let seed = self.tie_break_seed.unwrap_or_default();
+                rank(standing, |id| draw(seed, id))
+            }
+        };
+
+        self.results(placings, breakdowns)
+    }
+
+    /// Results from a standing the scoring method already ordered, settling
+    /// ties in its own rounds by `tie_order`.
+    fn ordered_results(
+        &self,
+        standing: Vec<(NominationID, f64)>,
+        breakdowns: &Breakdowns,
+    ) -> Results {
+        let placings = standing
             .into_iter()
-            .map(|(nomination_id, score)| Result {
-                nomination_id: nomination_id.clone(),
-                nomination_text: self.nominations[&nomination_id].text.clone(),
-                score: score.to_string(),
+            .enumerate()
+            .map(|(n, (nomination_id, score))| Placing {
+                nomination_id,
+                score,
+                rank: n + 1,
+                tied: false,
             })
             .collect();
+        self.results(placings, breakdowns)
+    }
+
+    fn results(&self, placings: Vec<Placing>, breakdowns: &Breakdowns) -> Results {
+        let total: f64 = placings.iter().map(|placing| placing.score).sum();
+        let shares = !(self.ballot_type == BallotType::Ranked
+            && matches!(
+                self.scoring_method,
+                ScoringMethodKind::Schulze | ScoringMethodKind::SingleTransferableVote
+            ));
+        placings
+            .into_iter()
+            .filter_map(|placing| {
+                // ballots may still name a nomination removed since
+                let nomination = self.nominations.get(&placing.nomination_id)?;
+                let breakdown = breakdowns
+                    .get(&placing.nomination_id)
+                    .cloned()
+                    .unwrap_or_default();
+                Some(Result {
+                    nomination_text: nomination.text.clone(),
+                    nomination_id: placing.nomination_id,
+                    share: match (shares, total > 0.0) {
+                        (false, _) => None,
+                        (true, true) => Some(placing.score / total),
+                        (true, false) => Some(0.0),
+                    },
+                    score: placing.score,
+                    rank: placing.rank,
+                    tied: placing.tied,
+                    histogram: breakdown.histogram,
+                    ballots: breakdown.ballots,
+                })
+            })
+            .collect()
+    }
+
+    /// Where every ballot placed each nomination, over histograms of the
+    /// same length for every nomination of the poll.
+    fn breakdowns(&self) -> Breakdowns {
+        let marks = match self.ballot_type {
+            BallotType::Ranked => self.votes_per_voter,
+            BallotType::Approval => self.nominations.len(),
+            BallotType::Score => MAX_SCORE as usize + 1,
+            BallotType::Cumulative => self.credit_cost.max_votes(self.credit_budget) + 1,
+        };
+        let mut breakdowns: Breakdowns = self
+            .nominations
+            .keys()
+            .map(|id| (id.clone(), Breakdown::new(marks)))
+            .collect();
+        let mut add = |id: &NominationID, mark: usize| {
+            breakdowns
+                .entry(id.clone())
+                .or_insert_with(|| Breakdown::new(marks))
+                .add(mark);
+        };
+        match self.ballot_type {
+            BallotType::Ranked => {
+                for ballot in self.rankings.values() {
+                    for (n, id) in ballot.iter().enumerate() {
+                        add(id, n);
+                    }
+                }
+            }
+            BallotType::Approval => {
+                for ballot in self.approvals.values() {
+                    let approved: HashSet<&NominationID> = ballot.iter().collect();
+                    let Some(others) = approved.len().checked_sub(1) else {
+                        continue;
+                    };
+                    for id in approved {
+                        add(id, others);
+                    }
+                }
+            }
+            BallotType::Score => {
+                for (id, score) in self.scores.values().flatten() {
+                    add(id, *score as usize);
+                }
+            }
+            BallotType::Cumulative => {
+                for (id, votes) in self.allocations.values().flatten() {
+                    add(id, *votes as usize);
+                }
+            }
+        }
+        breakdowns
+    }
 
-        // 3. Sort values by score in descending order
-        results.sort_by(|res1, res2| {
-            let score1 = res1.score.parse::<f64>().unwrap();
-            let score2 = res2.score.parse::<f64>().unwrap();
-            score1.partial_cmp(&score2).unwrap()
+    /// Ballots ranking each nomination first or giving it their highest
+    /// mark.
+    fn first_places(&self) -> HashMap<&NominationID, usize> {
+        let mut first_places: HashMap<&NominationID, usize> = HashMap::new();
+        let mut count = |ids: Vec<&'_ NominationID>| {
+            for id in ids {
+                if let Some((id, _)) = self.nominations.get_key_value(id) {
+                    *first_places.entry(id).or_insert(0) += 1;
+                }
+            }
+        };
+        match self.ballot_type {
+            BallotType::Ranked => self
+                .rankings
+                .values()
+                .for_each(|ballot| count(ballot.iter().take(1).collect())),
+            BallotType::Approval => self.approvals.values().for_each(|ballot| {
+                let approved: HashSet<&NominationID> = ballot.iter().collect();
+                count(approved.into_iter().collect())
+            }),
+            BallotType::Score => self
+                .scores
+                .values()
+                .for_each(|ballot| count(highest(ballot))),
+            BallotType::Cumulative => self
+                .allocations
+                .values()
+                .for_each(|ballot| count(highest(ballot))),
+        }
+        first_places
+    }
+
+    /// Every nomination in the order the poll's tie-break settles ties in,
+    /// for tallies that count in rounds. What the policy leaves tied goes to
+    /// the earliest nomination, then the lowest id.
+    fn tie_order(&self) -> TieOrder {
+        let first_places = self.first_places();
+        let seed = self.tie_break_seed.unwrap_or_default();
+        let mut nominations: Vec<(&NominationID, &Nomination)> = self.nominations.iter().collect();
+        nominations.sort_by_cached_key(|(id, nomination)| {
+            let (first_places, draw) = match self.tie_break {
+                TieBreak::FirstPlaces => (first_places.get(*id).copied().unwrap_or(0), 0),
+                TieBreak::Random => (0, draw(seed, id)),
+                TieBreak::KeepTied | TieBreak::EarliestNomination => (0, 0),
+            };
+            (Reverse(first_places), draw, nomination.created_at, *id)
         });
+        nominations
+            .into_iter()
+            .enumerate()
+            .map(|(n, (id, _))| (id.clone(), n))
+            .collect()
+    }
 
-        results
+    fn get_estimation_results(&self) -> (Results, Option<TallyReport>) {
+        let standing = self
+            .nominations
+            .keys()
+            .map(|nomination_id| {
+                let average = self
+                    .estimates
+                    .get(nomination_id)
+                    .and_then(|summary| summary.average)
+                    .unwrap_or_default();
+                (nomination_id.clone(), average)
+            })
+            .collect();
+
+        (self.ranked_results(standing, &Breakdowns::new()), None)
+    }
+
+    fn get_cumulative_results(&self) -> (Results, Option<TallyReport>) {
+        let standing = cumulative(&self.nominations, &self.allocations);
+        let results = self.ranked_results(as_scores(standing), &self.breakdowns());
+        (results, None)
+    }
+
+    fn get_star_results(&self) -> (Results, Option<TallyReport>) {
+        let (standing, report) = star(&self.nominations, &self.scores, &self.tie_order());
+        let results = self.ordered_results(as_scores(standing), &self.breakdowns());
+        (results, Some(TallyReport::Star(report)))
+    }
+
+    fn get_approval_results(&self) -> (Results, Option<TallyReport>) {
+        let standing = approval(&self.nominations, &self.approvals);
+        let results = self.ranked_results(as_scores(standing), &self.breakdowns());
+        (results, None)
+    }
+
+    fn get_runoff_results(&self) -> (Results, Option<TallyReport>) {
+        let (standing, rounds) =
+            instant_runoff(&self.nominations, &self.rankings, &self.tie_order());
+        let results = self.ordered_results(as_scores(standing), &self.breakdowns());
+        (results, Some(TallyReport::InstantRunoff { rounds }))
+    }
+
+    fn get_schulze_results(&self) -> (Results, Option<TallyReport>) {
+        let (standing, report) = schulze(&self.nominations, &self.rankings);
+        let results = self.ranked_results(as_scores(standing), &self.breakdowns());
+        (results, Some(TallyReport::Schulze(report)))
+    }
+
+    fn get_stv_results(&self) -> (Results, Option<TallyReport>) {
+        let (standing, report) = single_transferable_vote(
+            &self.nominations,
+            &self.rankings,
+            self.seats,
+            &self.tie_order(),
+        );
+        let results = self.ordered_results(standing, &self.breakdowns());
+        (results, Some(TallyReport::SingleTransferableVote(report)))
     }
 }
 
+/// Where the ballots placed a nomination.
+#[derive(Debug, Clone, Default)]
+struct Breakdown {
+    histogram: Vec<usize>,
+    ballots: usize,
+}
+
+impl Breakdown {
+    fn new(marks: usize) -> Self {
+        Breakdown {
+            histogram: vec![0; marks],
+            ballots: 0,
+        }
+    }
+
+    fn add(&mut self, mark: usize) {
+        if self.histogram.len() <= mark {
+            self.histogram.resize(mark + 1, 0);
+        }
+        self.histogram[mark] += 1;
+        self.ballots += 1;
+    }
+}
+
+type Breakdowns = HashMap<NominationID, Breakdown>;
+
+fn as_scores(standing: Vec<(NominationID, usize)>) -> Vec<(NominationID, f64)> {
+    standing
+        .into_iter()
+        .map(|(id, score)| (id, score as f64))
+        .collect()
+}
+
+/// Nominations given the highest non zero mark of a ballot.
+fn highest<T: Copy + Ord + Default>(ballot: &HashMap<NominationID, T>) -> Vec<&NominationID> {
+    let Some(max) = ballot.values().copied().max() else {
+        return Vec::new();
+    };
+    if max == T::default() {
+        return Vec::new();
+    }
+    ballot
+        .iter()
+        .filter(|(_, mark)| **mark == max)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+fn default_seats() -> usize {
+    1
+}
+
+fn default_credit_budget() -> usize {
+    100
+}
+
 impl TryFrom<String> for Poll {
     type Error = Error;
 
@@ -118,6 +794,61 @@ pub struct AddPollReq {
     pub votes_per_voter: usize,
     #[validate(length(min = 1, max = 25, message = "Can not be empty"))]
     pub name: String,
+    #[serde(default)]
+    pub kind: PollKind,
+    #[serde(default)]
+    pub ballot_type: BallotType,
+    #[serde(default)]
+    pub scoring_method: ScoringMethodKind,
+    #[validate(range(min = 1, max = 5))]
+    pub rank_exactly: Option<usize>,
+    #[serde(default = "default_seats")]
+    #[validate(range(min = 1, max = 10))]
+    pub seats: usize,
+    #[serde(default = "default_credit_budget")]
+    #[validate(range(min = 1, max = 1000))]
+    pub credit_budget: usize,
+    #[serde(default)]
+    pub credit_cost: CreditCost,
+    #[serde(default)]
+    pub deck: Deck,
+    /// Most first places for the tallies that count in rounds, ties kept
+    /// for the others, if not set.
+    pub tie_break: Option<TieBreak>,
+    #[serde(default)]
+    pub secret_ballots: bool,
+    #[serde(default)]
+    pub close_when_all_voted: bool,
+    #[validate(custom = "validate_quorum")]
+    pub quorum: Option<Quorum>,
+    pub open_at: Option<i64>,
+    pub voting_starts_at: Option<i64>,
+    /// Needed to join the poll, if set.
+    #[validate(length(min = 1, max = 128, message = "Can not be empty"))]
+    pub passphrase: Option<String>,
+}
+
+impl AddPollReq {
+    /// Whether the poll is tallied in rounds, instant-runoff, single
+    /// transferable vote or STAR, which settle ties as they count.
+    pub fn counts_in_rounds(&self) -> bool {
+        self.kind == PollKind::Ranking
+            && (self.ballot_type == BallotType::Score
+                || (self.ballot_type == BallotType::Ranked
+                    && matches!(
+                        self.scoring_method,
+                        ScoringMethodKind::InstantRunoff
+                            | ScoringMethodKind::SingleTransferableVote
+                    )))
+    }
+}
+
+fn validate_quorum(quorum: &Quorum) -> std::result::Result<(), ValidationError> {
+    match quorum {
+        Quorum::Voters(0) | Quorum::Percent(0) => Err(ValidationError::new("quorum_empty")),
+        Quorum::Percent(percent) if *percent > 100 => Err(ValidationError::new("quorum_percent")),
+        _ => Ok(()),
+    }
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -127,18 +858,322 @@ pub struct JoinPollReq {
 
     #[validate(length(min = 1, max = 25, message = "Can not be empty"))]
     pub name: String,
+
+    /// Required by polls created with a passphrase.
+    #[validate(length(max = 128))]
+    pub passphrase: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct AddNominationReq {
     #[validate(length(min = 1, max = 100, message = "Can not be empty"))]
     pub text: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddPollResp {
     pub poll: Poll,
     pub access_token: String,
 }
 
 pub type JoinPollResp = AddPollResp;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(tie_break: TieBreak, ballots: &[&[&str]]) -> Poll {
+        let mut poll = Poll::new(
+            "ABCDEF".to_string(),
+            "topic".to_string(),
+            3,
+            "u".to_string(),
+        );
+        poll.tie_break = tie_break;
+        poll.scoring_method = ScoringMethodKind::Borda;
+        for (n, id) in ["a", "b", "c"].iter().enumerate() {
+            let nomination = Nomination {
+                text: id.to_string(),
+                user_id: "u".to_string(),
+                created_at: n as i64,
+            };
+            poll.nominations.insert(id.to_string(), nomination);
+        }
+        for (n, ballot) in ballots.iter().enumerate() {
+            let ballot = ballot.iter().map(|id| id.to_string()).collect();
+            poll.rankings.insert(format!("u{n}"), ballot);
+        }
+        poll
+    }
+
+    #[test]
+    fn test_get_results_should_sort_descending_and_keep_ties() {
+        // Borda with 3 votes: a 3+2, b 2+3, c 1+1
+        let poll = poll(TieBreak::KeepTied, &[&["a", "b", "c"], &["b", "a", "c"]]);
+
+        let (results, _) = poll.get_results();
+
+        let ranks: Vec<(&str, usize, bool)> = results
+            .iter()
+            .map(|res| (res.nomination_id.as_str(), res.rank, res.tied))
+            .collect();
+        assert_eq!(vec![("a", 1, true), ("b", 1, true), ("c", 3, false)], ranks);
+    }
+
+    #[test]
+    fn test_get_results_should_skip_removed_nominations() {
+        let poll = poll(TieBreak::KeepTied, &[&["z", "a"]]);
+
+        let (results, _) = poll.get_results();
+
+        assert_eq!(
+            vec!["a", "b", "c"],
+            results
+                .iter()
+                .map(|res| res.nomination_id.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_get_results_should_score_unranked_nominations_zero() {
+        let poll = poll(TieBreak::KeepTied, &[&["a"]]);
+
+        let (results, _) = poll.get_results();
+
+        let scores: Vec<(&str, f64, usize, bool)> = results
+            .iter()
+            .map(|res| (res.nomination_id.as_str(), res.score, res.rank, res.tied))
+            .collect();
+        assert_eq!(
+            vec![
+                ("a", 3.0, 1, false),
+                ("b", 0.0, 2, true),
+                ("c", 0.0, 2, true)
+            ],
+            scores
+        );
+        assert_eq!(vec![0, 0, 0], results[1].histogram);
+    }
+
+    #[test]
+    fn test_get_results_should_break_down_positions() {
+        let poll = poll(TieBreak::KeepTied, &[&["a", "b", "c"], &["b", "a", "c"]]);
+
+        let (results, _) = poll.get_results();
+
+        assert_eq!(5.0, results[0].score);
+        assert_eq!(vec![1, 1, 0], results[0].histogram);
+        assert_eq!(2, results[0].ballots);
+        assert_eq!(vec![0, 0, 2], results[2].histogram);
+        assert_eq!(Some(2.0 / 12.0), results[2].share);
+    }
+
+    #[test]
+    fn test_get_results_should_not_share_schulze_wins() {
+        let mut poll = poll(TieBreak::KeepTied, &[&["a"], &["b", "a", "c"]]);
+        poll.scoring_method = ScoringMethodKind::Schulze;
+
+        let (results, _) = poll.get_results();
+
+        assert!(results.iter().all(|res| res.share.is_none()));
+        assert!(results.iter().all(|res| res.histogram.len() == 3));
+    }
+
+    #[test]
+    fn test_get_results_should_score_same_positions_alike() {
+        // every nomination ranked once at each position, whatever the order
+        // the ballots are counted in
+        let ballots: [&[&str]; 3] = [&["a", "b", "c"], &["b", "c", "a"], &["c", "a", "b"]];
+        let mut reversed = ballots;
+        reversed.reverse();
+        for ballots in [ballots, reversed] {
+            let mut poll = poll(TieBreak::KeepTied, &ballots);
+            poll.scoring_method = ScoringMethodKind::Dowdall;
+
+            let (results, _) = poll.get_results();
+
+            assert!(results.iter().all(|res| res.rank == 1 && res.tied));
+            assert!(results.iter().all(|res| res.score == results[0].score));
+        }
+    }
+
+    #[test]
+    fn test_get_results_should_break_ties_by_first_places() {
+        // a 3+2+2, b 1+3+3: tied at 7, b ranked first twice
+        let poll = poll(
+            TieBreak::FirstPlaces,
+            &[&["a", "c", "b"], &["b", "a", "c"], &["b", "a", "c"]],
+        );
+
+        let (results, _) = poll.get_results();
+
+        assert_eq!("b", results[0].nomination_id);
+        assert_eq!((2, false), (results[1].rank, results[1].tied));
+    }
+
+    #[test]
+    fn test_get_results_should_settle_runoff_ties_by_policy() {
+        let mut poll = poll(TieBreak::EarliestNomination, &[&["a"], &["c"]]);
+        poll.scoring_method = ScoringMethodKind::InstantRunoff;
+        poll.nominations.get_mut("c").unwrap().created_at = -1;
+
+        let (results, _) = poll.get_results();
+
+        assert_eq!("c", results[0].nomination_id);
+        assert_eq!("a", results[1].nomination_id);
+    }
+
+    #[test]
+    fn test_view_for_should_hide_other_ballots_when_secret() {
+        let mut poll = poll(TieBreak::KeepTied, &[&["a", "b", "c"], &["b", "a", "c"]]);
+
+        let view = poll.clone().view_for("u0");
+        assert_eq!(2, view.rankings.len());
+
+        poll.secret_ballots = true;
+        let view = poll.view_for("u0");
+        assert_eq!(vec!["u0"], view.rankings.keys().collect::<Vec<_>>());
+        assert_eq!(
+            BTreeSet::from(["u0".to_string(), "u1".to_string()]),
+            view.voted
+        );
+    }
+
+    #[test]
+    fn test_view_for_should_only_show_own_card_until_revealed() {
+        let mut poll = poll(TieBreak::KeepTied, &[]);
+        poll.estimation.cards = HashMap::from([
+            ("u0".to_string(), "3".to_string()),
+            ("u1".to_string(), "8".to_string()),
+        ]);
+
+        let view = poll.clone().view_for("u0");
+        assert_eq!("3", view.estimation.cards["u0"]);
+        assert_eq!("", view.estimation.cards["u1"]);
+
+        poll.estimation.revealed = true;
+        let view = poll.view_for("u0");
+        assert_eq!("8", view.estimation.cards["u1"]);
+    }
+
+    #[test]
+    fn test_check_rankings_should_reject_malformed_ballots() {
+        let mut poll = poll(TieBreak::KeepTied, &[]);
+        poll.votes_per_voter = 2;
+        let ballot =
+            |ids: &[&str]| -> RankingList { ids.iter().map(|id| id.to_string()).collect() };
+
+        assert_eq!(Ok(()), poll.check_rankings(&ballot(&["a", "b"])));
+        assert_eq!(Err(Error::EmptyBallot), poll.check_rankings(&ballot(&[])));
+        assert_eq!(
+            Err(Error::UnknownNomination),
+            poll.check_rankings(&ballot(&["a", "z"]))
+        );
+        assert_eq!(
+            Err(Error::DuplicateRanking),
+            poll.check_rankings(&ballot(&["a", "a"]))
+        );
+        assert_eq!(
+            Err(Error::TooManyRankings { max: 2 }),
+            poll.check_rankings(&ballot(&["a", "b", "c"]))
+        );
+
+        poll.rank_exactly = Some(2);
+        assert_eq!(
+            Err(Error::WrongRankingCount { expected: 2 }),
+            poll.check_rankings(&ballot(&["a"]))
+        );
+    }
+
+    #[test]
+    fn test_progress_should_list_waiting_participants() {
+        let mut poll = poll(TieBreak::KeepTied, &[&["a", "b", "c"]]);
+        for id in ["u0", "u1", "u2"] {
+            poll.participants.insert(id.to_string(), id.to_string());
+        }
+
+        let progress = poll.progress();
+
+        assert_eq!(vec!["u0".to_string()], progress.voted);
+        assert_eq!(vec!["u1".to_string(), "u2".to_string()], progress.waiting);
+        assert_eq!((1, 3), (progress.voted_count, progress.total));
+    }
+
+    #[test]
+    fn test_check_quorum_should_round_percent_up() {
+        let mut poll = poll(TieBreak::KeepTied, &[&["a", "b", "c"]]);
+        for id in ["u0", "u1", "u2"] {
+            poll.participants.insert(id.to_string(), id.to_string());
+        }
+
+        assert_eq!(Ok(()), poll.check_quorum());
+
+        poll.quorum = Some(Quorum::Percent(50));
+        assert_eq!(
+            Err(Error::NoQuorum {
+                voted: 1,
+                required: 2
+            }),
+            poll.check_quorum()
+        );
+
+        poll.quorum = Some(Quorum::Voters(1));
+        assert_eq!(Ok(()), poll.check_quorum());
+    }
+
+    #[test]
+    fn test_runoff_should_keep_top_nominations() {
+        // Borda with 3 votes: a 3+2+3, b 2+3+1, c 1+1+2
+        let mut poll = poll(
+            TieBreak::KeepTied,
+            &[&["a", "b", "c"], &["b", "a", "c"], &["a", "c", "b"]],
+        );
+        poll.participants.insert("u0".to_string(), "u0".to_string());
+        (poll.results, _) = poll.get_results();
+
+        let runoff = poll.runoff("GHIJKL".to_string(), 2).unwrap();
+
+        let mut nominations: Vec<&String> = runoff.nominations.keys().collect();
+        nominations.sort();
+        assert_eq!(vec!["a", "b"], nominations);
+        assert_eq!(Some("ABCDEF".to_string()), runoff.runoff_of);
+        assert_eq!(PollPhase::Voting, runoff.phase);
+        assert_eq!(2, runoff.votes_per_voter);
+        assert_eq!(poll.participants, runoff.participants);
+        assert!(runoff.rankings.is_empty());
+
+        assert_eq!(
+            Err(Error::RunoffTooSmall),
+            poll.runoff("GHIJKL".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn test_runoff_should_keep_bans() {
+        let mut poll = poll(TieBreak::KeepTied, &[&["a", "b", "c"]]);
+        poll.banned.insert("u9".to_string());
+        (poll.results, _) = poll.get_results();
+
+        let runoff = poll.runoff("GHIJKL".to_string(), 2).unwrap();
+
+        assert_eq!(poll.banned, runoff.banned);
+    }
+
+    #[test]
+    fn test_require_should_rank_roles() {
+        let mut poll = poll(TieBreak::KeepTied, &[]);
+        poll.moderators.insert("m".to_string());
+
+        assert_eq!(Ok(()), poll.require("u", Role::Admin));
+        assert_eq!(Ok(()), poll.require("m", Role::Moderator));
+        assert_eq!(
+            Err(Error::AdminPrivilegesRequired),
+            poll.require("m", Role::Admin)
+        );
+        assert_eq!(
+            Err(Error::ModeratorPrivilegesRequired),
+            poll.require("v", Role::Moderator)
+        );
+    }
+}
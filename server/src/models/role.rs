@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// What a participant may do in a poll. Every role may do what the roles
+/// before it may.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Voter,
+    /// Runs the poll: nominations, voting and reminding voters.
+    Moderator,
+    /// Also closes and cancels the poll, removes participants, starts
+    /// runoffs and hands out roles.
+    Admin,
+}
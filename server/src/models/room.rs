@@ -1,4 +1,4 @@
-use crate::models::NominationID;
+use crate::models::WebSocketEvent;
 use dashmap::DashMap;
 use tokio::sync::broadcast;
 use tokio::sync::broadcast::{Receiver, Sender};
@@ -9,6 +9,8 @@ pub struct RoomClient {
     pub addr: String,
     pub name: String,
     pub join_time: i64,
+    /// Watching only, see `Authed.spectator`.
+    pub spectator: bool,
 }
 
 pub type Room = DashMap<String, Vote>;
@@ -16,18 +18,20 @@ pub type Room = DashMap<String, Vote>;
 #[derive(Debug, Clone)]
 pub struct Vote {
     pub room_id: String,
-    pub list: Vec<NominationID>,
+    /// Keyed by connection, as a user may have several sockets open.
     pub clients: DashMap<String, RoomClient>,
 
-    pub sender: Sender<String>,
+    pub sender: Sender<WebSocketEvent>,
 }
 
 impl Vote {
-    pub fn broadcast(&mut self, message: String) {
-        let _ = self.sender.send(message);
+    /// Sends `event` to every client of the room, which sees it through
+    /// `WebSocketEvent::view_for`.
+    pub fn broadcast(&mut self, event: WebSocketEvent) {
+        let _ = self.sender.send(event);
     }
 
-    pub fn subscribe(&self) -> Receiver<String> {
+    pub fn subscribe(&self) -> Receiver<WebSocketEvent> {
         self.sender.subscribe()
     }
 }
@@ -45,19 +49,23 @@ impl Rooms {
             broadcast_capacity,
         }
     }
-    pub async fn add_client(&self, room_id: String, client: RoomClient) -> Vote {
+    pub async fn add_client(
+        &self,
+        room_id: String,
+        connection_id: String,
+        client: RoomClient,
+    ) -> Vote {
         if let Some(room) = self.room.get_mut(&room_id) {
-            room.clients.insert(client.id.clone(), client);
+            room.clients.insert(connection_id, client);
             return room.clone();
         }
 
         let (tx, _rx) = broadcast::channel(self.broadcast_capacity);
         let clients = DashMap::new();
-        clients.insert(client.id.clone(), client);
+        clients.insert(connection_id, client);
         let room = Vote {
             room_id: room_id.clone(),
             clients,
-            list: Vec::new(),
             sender: tx,
         };
         self.room.insert(room_id.clone(), room.clone());
@@ -65,53 +73,103 @@ impl Rooms {
         room
     }
 
+    /// Sends `event` to the room, if anyone is in it.
+    pub async fn broadcast(&self, room_id: String, event: WebSocketEvent) {
+        if let Some(mut room) = self.room.get_mut(&room_id) {
+            room.broadcast(event);
+        }
+    }
+
+    /// Moves the room and its clients to `new_room_id`. Subscribers keep
+    /// receiving what is broadcast to it.
+    pub async fn move_room(&self, room_id: String, new_room_id: String) {
+        if let Some((_, mut room)) = self.room.remove(&room_id) {
+            room.room_id = new_room_id.clone();
+            self.room.insert(new_room_id, room);
+        }
+    }
+
     pub async fn remove(&self, room_id: String) {
         self.room.remove(&room_id);
     }
 
-    pub async fn remove_client(&self, room_id: String, client_id: String) {
+    /// Removes one connection, the user may still have others open.
+    pub async fn remove_client(&self, room_id: String, connection_id: String) {
         if let Some(room) = self.room.get_mut(&room_id) {
-            room.clients.remove(&client_id);
+            room.clients.remove(&connection_id);
         }
     }
 
+    /// Removes every connection of the user.
+    pub async fn remove_user(&self, room_id: String, client_id: String) {
+        if let Some(room) = self.room.get_mut(&room_id) {
+            room.clients.retain(|_, client| client.id != client_id);
+        }
+    }
+
+    /// One of the user's connections, if any is open.
     pub async fn get_client(&self, room_id: String, client_id: String) -> Option<RoomClient> {
         if let Some(room) = self.room.get(&room_id) {
-            if let Some(client) = room.clients.get(&client_id) {
-                return Some(client.clone());
-            }
-            return None;
+            return room
+                .clients
+                .iter()
+                .find(|client| client.id == client_id)
+                .map(|client| client.clone());
         }
         None
     }
 
-    pub async fn add_nomination(&self, room_id: String, nomination_id: NominationID) {
-        if let Some(mut room) = self.room.get_mut(&room_id) {
-            room.list.push(nomination_id);
+    /// Clients connected to the room, once per connection.
+    pub async fn clients(&self, room_id: String) -> Vec<RoomClient> {
+        match self.room.get(&room_id) {
+            Some(room) => room.clients.iter().map(|client| client.clone()).collect(),
+            None => Vec::new(),
         }
     }
+}
 
-    pub async fn remove_nomination(&self, room_id: String, nomination_id: NominationID) {
-        if let Some(mut room) = self.room.get_mut(&room_id) {
-            room.list.retain(|id| id != &nomination_id);
-        }
+impl Default for Rooms {
+    fn default() -> Self {
+        Self::new(100)
     }
+}
 
-    pub async fn contains_nomination(
-        &self,
-        room_id: String,
-        nomination_ids: Vec<NominationID>,
-    ) -> bool {
-        if let Some(room) = self.room.get_mut(&room_id) {
-            return nomination_ids.iter().all(|x| room.list.contains(x));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(id: &str) -> RoomClient {
+        RoomClient {
+            id: id.to_string(),
+            addr: "127.0.0.1:3000".to_string(),
+            name: id.to_string(),
+            join_time: 0,
+            spectator: false,
         }
-
-        false
     }
-}
 
-impl Default for Rooms {
-    fn default() -> Self {
-        Self::new(100)
+    #[tokio::test]
+    async fn test_remove_client_should_keep_other_connections() {
+        let rooms = Rooms::default();
+        let room_id = "ABCDEF".to_string();
+        for connection_id in ["c1", "c2"] {
+            rooms
+                .add_client(room_id.clone(), connection_id.to_string(), client("u1"))
+                .await;
+        }
+        rooms
+            .add_client(room_id.clone(), "c3".to_string(), client("u2"))
+            .await;
+
+        rooms.remove_client(room_id.clone(), "c1".to_string()).await;
+        assert!(rooms
+            .get_client(room_id.clone(), "u1".to_string())
+            .await
+            .is_some());
+
+        rooms.remove_user(room_id.clone(), "u1".to_string()).await;
+        let clients = rooms.clients(room_id).await;
+        assert_eq!(1, clients.len());
+        assert_eq!("u2", clients[0].id);
     }
 }
@@ -1,26 +1,97 @@
 use serde::{Deserialize, Serialize};
 
-use super::{AddNominationReq, NominationID, Poll, RankingList};
+use super::{
+    AddNominationReq, AllocationList, ApprovalList, JoinPollResp, NominationID, Poll, RankingList,
+    Role, ScoreList, UserID, VotingProgress,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum WebSocketEvent {
     Exception(String),
     PollUpdated(Box<Poll>),
+    /// Removes and bans a participant, admin only.
     RemoveParticipant(String),
+    /// A participant was removed. Their socket is sent `Removed` instead
+    /// and closed.
+    ParticipantRemoved(UserID),
+    Removed,
+    /// Makes a participant a moderator or a voter, or hands them the admin
+    /// role, admin only.
+    SetRole {
+        user_id: UserID,
+        role: Role,
+    },
+    /// Hands the admin role over, admin only.
+    TransferAdmin(UserID),
+    /// Adds a nomination, opening nominations if the poll is still in the
+    /// lobby.
     Nomination(AddNominationReq),
     RemoveNomination(NominationID),
+    /// Opens nominations, moderator only.
+    OpenNominations,
     StartVote,
+    /// Starts voting, closing the poll at the given unix timestamp in
+    /// milliseconds.
+    StartVoteUntil(i64),
+    /// Seconds left before the poll closes, sent every second.
+    Countdown(i64),
     SubmitRankings(RankingList),
+    SubmitApprovals(ApprovalList),
+    SubmitScores(ScoreList),
+    SubmitAllocations(AllocationList),
+    /// Starts estimating a nomination, dropping the cards played so far.
+    EstimateItem(NominationID),
+    PlayCard(String),
+    RevealCards,
+    VotingProgress(VotingProgress),
+    /// Reminds participants who have not voted yet, moderator or admin only.
+    Nudge,
+    /// Sent to the listed participants only, see `WebSocketEvent::is_for`.
+    Reminder(Vec<UserID>),
     CancelPoll,
+    /// Closes the poll and tallies it, admin only.
     ClosePoll,
+    /// Closes the poll even below quorum, see `Poll.no_quorum`.
+    ForceClosePoll,
     PollCancelled,
+    /// Starts a runoff between the nominations placed in the top N of the
+    /// results, admin only.
+    StartRunoff(usize),
+    /// Moves every socket of the room to the runoff, see
+    /// `WebSocketEvent::MovedToRunoff`.
+    RunoffStarted(Box<Poll>),
+    /// Sent to each client once its socket is in the runoff, with a token to
+    /// reconnect to it.
+    MovedToRunoff(Box<JoinPollResp>),
 }
 
 impl WebSocketEvent {
     pub fn message(self) -> String {
         serde_json::to_string(&self).unwrap()
     }
+
+    /// Whether the event should be sent to `user_id` at all.
+    pub fn is_for(&self, user_id: &str) -> bool {
+        match self {
+            WebSocketEvent::Reminder(user_ids) => user_ids.iter().any(|id| id == user_id),
+            _ => true,
+        }
+    }
+
+    /// The event as `user_id` may see it, see `Poll::view_for`.
+    pub fn view_for(self, user_id: &str) -> Self {
+        match self {
+            WebSocketEvent::PollUpdated(poll) => {
+                WebSocketEvent::PollUpdated(Box::new(poll.view_for(user_id)))
+            }
+            WebSocketEvent::MovedToRunoff(mut resp) => {
+                resp.poll = resp.poll.view_for(user_id);
+                WebSocketEvent::MovedToRunoff(resp)
+            }
+            event => event,
+        }
+    }
 }
 
 impl From<String> for WebSocketEvent {
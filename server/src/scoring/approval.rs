@@ -0,0 +1,48 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{Approvals, NominationID, Nominations};
+
+/// Counts approvals per nomination, every nomination of the poll included.
+///
+/// Returns the nominations from the most approved down.
+pub fn approval(nominations: &Nominations, approvals: &Approvals) -> Vec<(NominationID, usize)> {
+    let mut counts: HashMap<&NominationID, usize> = nominations.keys().map(|id| (id, 0)).collect();
+    for ballot in approvals.values() {
+        let approved: HashSet<&NominationID> = ballot.iter().collect();
+        for id in approved {
+            if let Some(count) = counts.get_mut(id) {
+                *count += 1;
+            }
+        }
+    }
+
+    let mut standing: Vec<(NominationID, usize)> = counts
+        .into_iter()
+        .map(|(id, count)| (id.clone(), count))
+        .collect();
+    standing.sort_by(|(id1, count1), (id2, count2)| count2.cmp(count1).then(id1.cmp(id2)));
+    standing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::fixtures::{ballots, nominations};
+
+    #[test]
+    fn test_approval_should_count_each_voter_once() {
+        let nominations = nominations(&["a", "b", "c"]);
+        let approvals = ballots(&[&["a", "b"], &["b", "b"]]);
+
+        let standing = approval(&nominations, &approvals);
+
+        assert_eq!(
+            vec![
+                ("b".to_string(), 2),
+                ("a".to_string(), 1),
+                ("c".to_string(), 0)
+            ],
+            standing
+        );
+    }
+}
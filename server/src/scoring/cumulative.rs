@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{AllocationList, Allocations, NominationID, Nominations};
+
+/// What votes cost on a cumulative ballot.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditCost {
+    /// Every vote costs one credit.
+    #[default]
+    Linear,
+    /// `n` votes for the same nomination cost `n²` credits.
+    Quadratic,
+}
+
+impl CreditCost {
+    /// Credits spent by a whole ballot.
+    pub fn ballot_cost(&self, allocations: &AllocationList) -> usize {
+        allocations
+            .values()
+            .map(|votes| {
+                let votes = *votes as usize;
+                match self {
+                    CreditCost::Linear => votes,
+                    CreditCost::Quadratic => votes.saturating_mul(votes),
+                }
+            })
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Most votes a ballot can give one nomination out of `budget` credits.
+    pub fn max_votes(&self, budget: usize) -> usize {
+        match self {
+            CreditCost::Linear => budget,
+            CreditCost::Quadratic => budget.isqrt(),
+        }
+    }
+}
+
+/// Sums the votes every nomination of the poll was given.
+///
+/// Returns the nominations from the most votes down.
+pub fn cumulative(
+    nominations: &Nominations,
+    allocations: &Allocations,
+) -> Vec<(NominationID, usize)> {
+    let mut votes: HashMap<&NominationID, usize> = nominations.keys().map(|id| (id, 0)).collect();
+    for ballot in allocations.values() {
+        for (id, count) in ballot {
+            if let Some(total) = votes.get_mut(id) {
+                *total += *count as usize;
+            }
+        }
+    }
+
+    let mut standing: Vec<(NominationID, usize)> = votes
+        .into_iter()
+        .map(|(id, total)| (id.clone(), total))
+        .collect();
+    standing.sort_by(|(id1, total1), (id2, total2)| total2.cmp(total1).then(id1.cmp(id2)));
+    standing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::fixtures::{marks, nominations};
+
+    #[test]
+    fn test_ballot_cost_should_square_quadratic_votes() {
+        let ballot: AllocationList = HashMap::from([("a".to_string(), 3), ("b".to_string(), 1)]);
+
+        assert_eq!(4, CreditCost::Linear.ballot_cost(&ballot));
+        assert_eq!(10, CreditCost::Quadratic.ballot_cost(&ballot));
+    }
+
+    #[test]
+    fn test_cumulative_should_sum_votes() {
+        let nominations = nominations(&["a", "b", "c"]);
+        let allocations = marks(&[&[("a", 3), ("b", 1)], &[("b", 4)]]);
+
+        let standing = cumulative(&nominations, &allocations);
+
+        assert_eq!(
+            vec![
+                ("b".to_string(), 5),
+                ("a".to_string(), 3),
+                ("c".to_string(), 0)
+            ],
+            standing
+        );
+    }
+}
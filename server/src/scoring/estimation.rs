@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::{Deck, EstimateSummary, UserID};
+
+/// Summarizes the cards played on an item.
+pub fn summarize(deck: Deck, cards: &HashMap<UserID, String>) -> EstimateSummary {
+    let mut spread: BTreeMap<String, usize> = BTreeMap::new();
+    for card in cards.values() {
+        *spread.entry(card.clone()).or_insert(0) += 1;
+    }
+
+    let values: Vec<f64> = cards.values().filter_map(|card| deck.value(card)).collect();
+    let average = (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64);
+    let estimate = average.and_then(|average| {
+        deck.cards()
+            .iter()
+            .filter_map(|card| deck.value(card).map(|value| (card, value)))
+            .min_by(|(_, value1), (_, value2)| {
+                (value1 - average)
+                    .abs()
+                    .total_cmp(&(value2 - average).abs())
+            })
+            .map(|(card, _)| card.to_string())
+    });
+
+    let mut positions: Vec<usize> = cards
+        .values()
+        .filter_map(|card| deck.position(card))
+        .collect();
+    positions.sort();
+    let mut outliers: Vec<UserID> = match positions.get(positions.len().saturating_sub(1) / 2) {
+        Some(&median) => cards
+            .iter()
+            .filter(|(_, card)| {
+                deck.position(card)
+                    .is_some_and(|position| position.abs_diff(median) > 1)
+            })
+            .map(|(user_id, _)| user_id.clone())
+            .collect(),
+        None => Vec::new(),
+    };
+    outliers.sort();
+
+    EstimateSummary {
+        spread,
+        average,
+        estimate,
+        outliers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(cards: &[&str]) -> HashMap<UserID, String> {
+        cards
+            .iter()
+            .enumerate()
+            .map(|(n, card)| (format!("u{n}"), card.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_summarize_should_find_outliers() {
+        let summary = summarize(Deck::Fibonacci, &cards(&["3", "5", "5", "21", "?"]));
+
+        assert_eq!(Some(&2), summary.spread.get("5"));
+        assert_eq!(Some(&1), summary.spread.get("?"));
+        assert_eq!(Some(8.5), summary.average);
+        assert_eq!(Some("8".to_string()), summary.estimate);
+        assert_eq!(vec!["u3".to_string()], summary.outliers);
+    }
+
+    #[test]
+    fn test_summarize_should_average_positions() {
+        let summary = summarize(Deck::TShirt, &cards(&["S", "M", "L"]));
+
+        assert_eq!(Some(3.0), summary.average);
+        assert_eq!(Some("M".to_string()), summary.estimate);
+        assert!(summary.outliers.is_empty());
+    }
+
+    #[test]
+    fn test_summarize_should_skip_unsure() {
+        let summary = summarize(Deck::Fibonacci, &cards(&["?"]));
+
+        assert_eq!(None, summary.average);
+        assert_eq!(None, summary.estimate);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::TieOrder;
+use crate::models::{NominationID, Nominations, Rankings};
+
+/// One counting round of instant-runoff.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IrvRound {
+    pub round: usize,
+    /// Ballots whose top continuing preference is each nomination.
+    pub counts: BTreeMap<NominationID, usize>,
+    /// Ballots with no continuing preference left.
+    pub exhausted: usize,
+    pub eliminated: Option<NominationID>,
+    /// Where the eliminated nomination's ballots went in the next round.
+    pub transfers: BTreeMap<NominationID, usize>,
+    pub winner: Option<NominationID>,
+}
+
+/// Runs instant-runoff over every nomination of the poll.
+///
+/// Returns the nominations from the winner down to the first one eliminated,
+/// each with its count in the last round it took part in, together with the
+/// report of every round. Without a single valid ballot there is no winner,
+/// and no standing. Ties left after first preferences go by `tie_order`.
+pub fn instant_runoff(
+    nominations: &Nominations,
+    rankings: &Rankings,
+    tie_order: &TieOrder,
+) -> (Vec<(NominationID, usize)>, Vec<IrvRound>) {
+    let mut continuing: Vec<NominationID> = nominations.keys().cloned().collect();
+    continuing.sort();
+
+    let mut rounds: Vec<IrvRound> = Vec::new();
+    // last round count of each eliminated nomination, in elimination order
+    let mut eliminated: Vec<(NominationID, usize)> = Vec::new();
+    let mut first_preferences: BTreeMap<NominationID, usize> = BTreeMap::new();
+
+    while !continuing.is_empty() {
+        let active: HashSet<&NominationID> = continuing.iter().collect();
+        let mut counts: BTreeMap<NominationID, usize> =
+            continuing.iter().map(|id| (id.clone(), 0)).collect();
+        let mut exhausted = 0;
+        for ballot in rankings.values() {
+            match ballot.iter().find(|id| active.contains(id)) {
+                Some(id) => *counts.get_mut(id).unwrap() += 1,
+                None => exhausted += 1,
+            }
+        }
+        if rounds.is_empty() {
+            first_preferences = counts.clone();
+        }
+
+        let valid: usize = counts.values().sum();
+        let round = rounds.len() + 1;
+        if valid == 0 {
+            rounds.push(IrvRound {
+                round,
+                counts,
+                exhausted,
+                eliminated: None,
+                transfers: BTreeMap::new(),
+                winner: None,
+            });
+            return (Vec::new(), rounds);
+        }
+
+        let leader = counts
+            .iter()
+            .max_by(|(id1, count1), (id2, count2)| {
+                count1
+                    .cmp(count2)
+                    .then(tie_order[*id2].cmp(&tie_order[*id1]))
+            })
+            .map(|(id, count)| (id.clone(), *count))
+            .unwrap();
+        if leader.1 * 2 > valid || continuing.len() == 1 {
+            rounds.push(IrvRound {
+                round,
+                counts: counts.clone(),
+                exhausted,
+                eliminated: None,
+                transfers: BTreeMap::new(),
+                winner: Some(leader.0.clone()),
+            });
+
+            // the winner first, the rest by their final count
+            let mut standing: Vec<(NominationID, usize)> = counts.into_iter().collect();
+            standing.sort_by(|(id1, count1), (id2, count2)| {
+                (id1 != &leader.0)
+                    .cmp(&(id2 != &leader.0))
+                    .then(count2.cmp(count1))
+                    .then(tie_order[id1].cmp(&tie_order[id2]))
+            });
+            standing.extend(eliminated.into_iter().rev());
+            return (standing, rounds);
+        }
+
+        // eliminate the lowest count, fewest first preferences on a tie,
+        // then the last in tie order
+        let (loser, loser_count) = counts
+            .iter()
+            .min_by(|(id1, count1), (id2, count2)| {
+                count1
+                    .cmp(count2)
+                    .then(first_preferences[*id1].cmp(&first_preferences[*id2]))
+                    .then(tie_order[*id2].cmp(&tie_order[*id1]))
+            })
+            .map(|(id, count)| (id.clone(), *count))
+            .unwrap();
+
+        let mut transfers: BTreeMap<NominationID, usize> = BTreeMap::new();
+        for ballot in rankings.values() {
+            let mut preferences = ballot.iter().filter(|id| active.contains(id));
+            if preferences.next() != Some(&loser) {
+                continue;
+            }
+            if let Some(next) = preferences.find(|id| *id != &loser) {
+                *transfers.entry(next.clone()).or_insert(0) += 1;
+            }
+        }
+        continuing.retain(|id| id != &loser);
+
+        rounds.push(IrvRound {
+            round,
+            counts,
+            exhausted,
+            eliminated: Some(loser.clone()),
+            transfers,
+            winner: None,
+        });
+        eliminated.push((loser, loser_count));
+    }
+
+    (eliminated, rounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::fixtures::{ballots, nominations, tie_order};
+
+    #[test]
+    fn test_instant_runoff_should_transfer_votes() {
+        let nominations = nominations(&["a", "b", "c"]);
+        let rankings = ballots(&[
+            &["a", "b"],
+            &["a", "c"],
+            &["b", "a"],
+            &["b", "a"],
+            &["c", "b"],
+        ]);
+
+        let (standing, rounds) =
+            instant_runoff(&nominations, &rankings, &tie_order(&["a", "b", "c"]));
+
+        assert_eq!(2, rounds.len());
+        assert_eq!(Some("c".to_string()), rounds[0].eliminated);
+        assert_eq!(BTreeMap::from([("b".to_string(), 1)]), rounds[0].transfers);
+        assert_eq!(Some("b".to_string()), rounds[1].winner);
+        assert_eq!(
+            vec![
+                ("b".to_string(), 3),
+                ("a".to_string(), 2),
+                ("c".to_string(), 1)
+            ],
+            standing
+        );
+    }
+
+    #[test]
+    fn test_instant_runoff_should_count_exhausted() {
+        let nominations = nominations(&["a", "b", "c"]);
+        let rankings = ballots(&[&["a"], &["a"], &["a"], &["b"], &["b"], &["c"]]);
+
+        let (standing, rounds) =
+            instant_runoff(&nominations, &rankings, &tie_order(&["a", "b", "c"]));
+
+        assert_eq!(1, rounds[1].exhausted);
+        assert_eq!("a", standing[0].0);
+    }
+
+    #[test]
+    fn test_instant_runoff_should_not_elect_without_ballots() {
+        let nominations = nominations(&["a", "b"]);
+
+        let (standing, rounds) =
+            instant_runoff(&nominations, &ballots(&[]), &tie_order(&["a", "b"]));
+        assert!(standing.is_empty());
+        assert_eq!(1, rounds.len());
+        assert_eq!(None, rounds[0].winner);
+
+        let (standing, rounds) = instant_runoff(
+            &nominations,
+            &ballots(&[&["c"], &[]]),
+            &tie_order(&["a", "b"]),
+        );
+        assert!(standing.is_empty());
+        assert_eq!(2, rounds[0].exhausted);
+    }
+
+    #[test]
+    fn test_instant_runoff_should_eliminate_by_tie_order() {
+        let nominations = nominations(&["a", "b"]);
+        let rankings = ballots(&[&["a"], &["b"]]);
+
+        let (standing, rounds) = instant_runoff(&nominations, &rankings, &tie_order(&["b", "a"]));
+
+        assert_eq!(Some("a".to_string()), rounds[0].eliminated);
+        assert_eq!("b", standing[0].0);
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+mod approval;
+mod cumulative;
+mod estimation;
+mod irv;
+mod positional;
+mod schulze;
+mod star;
+mod stv;
+mod ties;
+pub use approval::*;
+pub use cumulative::*;
+pub use estimation::*;
+pub use irv::*;
+pub use positional::*;
+pub use schulze::*;
+pub use star::*;
+pub use stv::*;
+pub use ties::*;
+
+/// Positional scoring: every ballot awards points to the nominations it
+/// ranks, depending only on where each one was ranked.
+pub trait ScoringMethod {
+    /// Points for the nomination at `position` (0 based) of a ballot.
+    fn points(&self, position: usize, votes_per_voter: usize) -> f64;
+}
+
+/// Scoring method chosen by the admin when creating a poll.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringMethodKind {
+    #[default]
+    Exponential,
+    Borda,
+    Dowdall,
+    Plurality,
+    InstantRunoff,
+    Schulze,
+    SingleTransferableVote,
+}
+
+impl ScoringMethodKind {
+    /// The positional method, `None` for methods that need whole ballots.
+    pub fn method(&self) -> Option<Box<dyn ScoringMethod>> {
+        match self {
+            ScoringMethodKind::Exponential => Some(Box::new(Exponential)),
+            ScoringMethodKind::Borda => Some(Box::new(Borda)),
+            ScoringMethodKind::Dowdall => Some(Box::new(Dowdall)),
+            ScoringMethodKind::Plurality => Some(Box::new(Plurality)),
+            ScoringMethodKind::InstantRunoff
+            | ScoringMethodKind::Schulze
+            | ScoringMethodKind::SingleTransferableVote => None,
+        }
+    }
+}
+
+/// How a closed poll was tallied, for methods that are more than a score per
+/// nomination. Stored next to the results.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum TallyReport {
+    InstantRunoff { rounds: Vec<IrvRound> },
+    Schulze(SchulzeReport),
+    SingleTransferableVote(StvReport),
+    Star(StarReport),
+}
+
+/// Builders shared by the tests of the tallies.
+#[cfg(test)]
+mod fixtures {
+    use std::collections::HashMap;
+
+    use super::TieOrder;
+    use crate::models::{Nomination, NominationID, Nominations, UserID};
+
+    /// Nominations with these ids, which are their text too.
+    pub fn nominations(ids: &[&str]) -> Nominations {
+        ids.iter()
+            .map(|id| {
+                (
+                    id.to_string(),
+                    Nomination {
+                        text: id.to_string(),
+                        user_id: "u".to_string(),
+                        created_at: 0,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Settles ties in the order `ids` are listed.
+    pub fn tie_order(ids: &[&str]) -> TieOrder {
+        ids.iter()
+            .enumerate()
+            .map(|(n, id)| (id.to_string(), n))
+            .collect()
+    }
+
+    /// Ballots listing nominations, ranked or approved, of voters `u0` on.
+    pub fn ballots(ballots: &[&[&str]]) -> HashMap<UserID, Vec<NominationID>> {
+        ballots
+            .iter()
+            .enumerate()
+            .map(|(n, ballot)| {
+                (
+                    format!("u{n}"),
+                    ballot.iter().map(|id| id.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Ballots giving nominations a score or votes, of voters `u0` on.
+    pub fn marks<T: Copy>(ballots: &[&[(&str, T)]]) -> HashMap<UserID, HashMap<NominationID, T>> {
+        ballots
+            .iter()
+            .enumerate()
+            .map(|(n, ballot)| {
+                (
+                    format!("u{n}"),
+                    ballot
+                        .iter()
+                        .map(|(id, mark)| (id.to_string(), *mark))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
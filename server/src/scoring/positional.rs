@@ -0,0 +1,78 @@
+use super::ScoringMethod;
+
+/// r_n = ((votes_per_voter - 0.5*n) / votes_per_voter)^(n+1)
+#[derive(Debug, Clone, Copy)]
+pub struct Exponential;
+
+impl ScoringMethod for Exponential {
+    fn points(&self, position: usize, votes_per_voter: usize) -> f64 {
+        let votes_per_voter = votes_per_voter as f64;
+        let n = position as f64;
+        ((votes_per_voter - 0.5 * n) / votes_per_voter).powf(n + 1.0)
+    }
+}
+
+/// Classic Borda count: first choice gets `votes_per_voter` points, each
+/// following choice one point less.
+#[derive(Debug, Clone, Copy)]
+pub struct Borda;
+
+impl ScoringMethod for Borda {
+    fn points(&self, position: usize, votes_per_voter: usize) -> f64 {
+        votes_per_voter.saturating_sub(position) as f64
+    }
+}
+
+/// Dowdall: the n-th choice gets 1/n points.
+#[derive(Debug, Clone, Copy)]
+pub struct Dowdall;
+
+impl ScoringMethod for Dowdall {
+    fn points(&self, position: usize, _votes_per_voter: usize) -> f64 {
+        1.0 / (position + 1) as f64
+    }
+}
+
+/// Plurality: only the first choice counts.
+#[derive(Debug, Clone, Copy)]
+pub struct Plurality;
+
+impl ScoringMethod for Plurality {
+    fn points(&self, position: usize, _votes_per_voter: usize) -> f64 {
+        if position == 0 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_points() {
+        assert_eq!(1.0, Exponential.points(0, 3));
+        assert_eq!((2.5_f64 / 3.0).powf(2.0), Exponential.points(1, 3));
+    }
+
+    #[test]
+    fn test_borda_points() {
+        assert_eq!(3.0, Borda.points(0, 3));
+        assert_eq!(1.0, Borda.points(2, 3));
+        assert_eq!(0.0, Borda.points(3, 3));
+    }
+
+    #[test]
+    fn test_dowdall_points() {
+        assert_eq!(1.0, Dowdall.points(0, 3));
+        assert_eq!(0.5, Dowdall.points(1, 3));
+    }
+
+    #[test]
+    fn test_plurality_points() {
+        assert_eq!(1.0, Plurality.points(0, 3));
+        assert_eq!(0.0, Plurality.points(1, 3));
+    }
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::{NominationID, Nominations, Rankings};
+
+/// Pairwise view of a Schulze tally. Rows and columns of both tables follow
+/// the order of `nominations`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SchulzeReport {
+    pub nominations: Vec<NominationID>,
+    /// `preferences[i][j]` is the number of voters preferring `i` over `j`.
+    pub preferences: Vec<Vec<usize>>,
+    /// `strongest_paths[i][j]` is the strength of the strongest path from
+    /// `i` to `j`.
+    pub strongest_paths: Vec<Vec<usize>>,
+    /// The nomination beating every other one head to head, if any.
+    pub condorcet_winner: Option<NominationID>,
+    /// Whether the head to head majorities contain a cycle.
+    pub has_cycle: bool,
+}
+
+/// Runs the Schulze method over every nomination of the poll. Nominations
+/// missing from a ballot count as tied last on it.
+///
+/// Returns every nomination with the number of others it beats, from the
+/// strongest down, together with the pairwise report.
+pub fn schulze(
+    nominations: &Nominations,
+    rankings: &Rankings,
+) -> (Vec<(NominationID, usize)>, SchulzeReport) {
+    let mut ids: Vec<NominationID> = nominations.keys().cloned().collect();
+    ids.sort();
+    let n = ids.len();
+
+    let mut preferences = vec![vec![0; n]; n];
+    for ballot in rankings.values() {
+        let ranks: Vec<usize> = ids
+            .iter()
+            .map(|id| {
+                ballot
+                    .iter()
+                    .position(|ranked| ranked == id)
+                    .unwrap_or(usize::MAX)
+            })
+            .collect();
+        for i in 0..n {
+            for j in 0..n {
+                if ranks[i] < ranks[j] {
+                    preferences[i][j] += 1;
+                }
+            }
+        }
+    }
+
+    // widest paths, Floyd-Warshall style
+    let mut strongest_paths = vec![vec![0; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            if i != j && preferences[i][j] > preferences[j][i] {
+                strongest_paths[i][j] = preferences[i][j];
+            }
+        }
+    }
+    for k in 0..n {
+        for i in 0..n {
+            if i == k {
+                continue;
+            }
+            for j in 0..n {
+                if j == i || j == k {
+                    continue;
+                }
+                let through_k = strongest_paths[i][k].min(strongest_paths[k][j]);
+                if through_k > strongest_paths[i][j] {
+                    strongest_paths[i][j] = through_k;
+                }
+            }
+        }
+    }
+
+    let beats = |i: usize, j: usize| preferences[i][j] > preferences[j][i];
+    let condorcet_winner = (0..n)
+        .find(|&i| (0..n).all(|j| i == j || beats(i, j)))
+        .map(|i| ids[i].clone());
+    let has_cycle = has_cycle(n, &beats);
+
+    let mut standing: Vec<(NominationID, usize)> = (0..n)
+        .map(|i| {
+            let wins = (0..n)
+                .filter(|&j| strongest_paths[i][j] > strongest_paths[j][i])
+                .count();
+            (ids[i].clone(), wins)
+        })
+        .collect();
+    standing.sort_by(|(id1, wins1), (id2, wins2)| wins2.cmp(wins1).then(id1.cmp(id2)));
+
+    let report = SchulzeReport {
+        nominations: ids,
+        preferences,
+        strongest_paths,
+        condorcet_winner,
+        has_cycle,
+    };
+    (standing, report)
+}
+
+/// Depth first search for a cycle in the "beats" graph.
+fn has_cycle(n: usize, beats: &dyn Fn(usize, usize) -> bool) -> bool {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        Visiting,
+        Done,
+    }
+
+    fn visit(i: usize, n: usize, beats: &dyn Fn(usize, usize) -> bool, marks: &mut [Mark]) -> bool {
+        marks[i] = Mark::Visiting;
+        for j in 0..n {
+            if i == j || !beats(i, j) {
+                continue;
+            }
+            if marks[j] == Mark::Visiting {
+                return true;
+            }
+            if marks[j] == Mark::New && visit(j, n, beats, marks) {
+                return true;
+            }
+        }
+        marks[i] = Mark::Done;
+        false
+    }
+
+    let mut marks = vec![Mark::New; n];
+    (0..n).any(|i| marks[i] == Mark::New && visit(i, n, beats, &mut marks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::fixtures::{ballots, nominations};
+
+    #[test]
+    fn test_schulze_should_find_condorcet_winner() {
+        let nominations = nominations(&["a", "b", "c"]);
+        // unranked "c" is tied last on the first two ballots
+        let rankings = ballots(&[&["a", "b"], &["a"], &["b", "c", "a"]]);
+
+        let (standing, report) = schulze(&nominations, &rankings);
+
+        assert_eq!(vec![0, 2, 2], report.preferences[0]);
+        assert_eq!(Some("a".to_string()), report.condorcet_winner);
+        assert!(!report.has_cycle);
+        assert_eq!(
+            vec![
+                ("a".to_string(), 2),
+                ("b".to_string(), 1),
+                ("c".to_string(), 0)
+            ],
+            standing
+        );
+    }
+
+    #[test]
+    fn test_schulze_should_detect_cycle() {
+        let nominations = nominations(&["a", "b", "c"]);
+        let rankings = ballots(&[
+            &["a", "b", "c"],
+            &["a", "b", "c"],
+            &["b", "c", "a"],
+            &["b", "c", "a"],
+            &["c", "a", "b"],
+            &["c", "a", "b"],
+            &["c", "a", "b"],
+        ]);
+
+        let (standing, report) = schulze(&nominations, &rankings);
+
+        assert_eq!(None, report.condorcet_winner);
+        assert!(report.has_cycle);
+        // a>b 5:2, b>c 4:3, c>a 5:2, dropping the weakest link b>c leaves c>a>b
+        assert_eq!(
+            vec![
+                ("c".to_string(), 2),
+                ("a".to_string(), 1),
+                ("b".to_string(), 0)
+            ],
+            standing
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::TieOrder;
+use crate::models::{NominationID, Nominations, Scores};
+
+/// Highest score a ballot can give a nomination.
+pub const MAX_SCORE: u8 = 5;
+
+/// Both rounds of a STAR (Score Then Automatic Runoff) tally.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StarReport {
+    /// Total score of every nomination, unscored counting as 0.
+    pub totals: BTreeMap<NominationID, usize>,
+    /// The two highest totals, which go to the runoff.
+    pub finalists: Vec<NominationID>,
+    /// Ballots scoring each finalist above the other one.
+    pub runoff: BTreeMap<NominationID, usize>,
+    /// Ballots scoring both finalists the same.
+    pub no_preference: usize,
+    pub winner: Option<NominationID>,
+}
+
+/// Runs STAR over every nomination of the poll. A runoff tie goes to the
+/// higher total score, and equal totals go by `tie_order`.
+///
+/// Returns the runoff winner, the other finalist and then the rest by total
+/// score, each with its total, together with the report of both rounds.
+/// Without a single ballot scoring one of the nominations there is no
+/// winner, and nothing is returned but the totals.
+pub fn star(
+    nominations: &Nominations,
+    scores: &Scores,
+    tie_order: &TieOrder,
+) -> (Vec<(NominationID, usize)>, StarReport) {
+    let mut totals: BTreeMap<NominationID, usize> =
+        nominations.keys().map(|id| (id.clone(), 0)).collect();
+    for ballot in scores.values() {
+        for (id, score) in ballot {
+            if let Some(total) = totals.get_mut(id) {
+                *total += *score as usize;
+            }
+        }
+    }
+
+    // nothing to count, nobody wins with no ballots
+    let counted = scores
+        .values()
+        .any(|ballot| ballot.keys().any(|id| totals.contains_key(id)));
+    if !counted {
+        let report = StarReport {
+            totals,
+            finalists: Vec::new(),
+            runoff: BTreeMap::new(),
+            no_preference: 0,
+            winner: None,
+        };
+        return (Vec::new(), report);
+    }
+
+    let mut standing: Vec<(NominationID, usize)> = totals
+        .iter()
+        .map(|(id, total)| (id.clone(), *total))
+        .collect();
+    standing.sort_by(|(id1, total1), (id2, total2)| {
+        total2.cmp(total1).then(tie_order[id1].cmp(&tie_order[id2]))
+    });
+
+    let finalists: Vec<NominationID> = standing.iter().take(2).map(|(id, _)| id.clone()).collect();
+    let mut runoff: BTreeMap<NominationID, usize> =
+        finalists.iter().map(|id| (id.clone(), 0)).collect();
+    let mut no_preference = 0;
+    if let [first, second] = finalists.as_slice() {
+        for ballot in scores.values() {
+            let score1 = ballot.get(first).copied().unwrap_or(0);
+            let score2 = ballot.get(second).copied().unwrap_or(0);
+            if score1 > score2 {
+                *runoff.get_mut(first).unwrap() += 1;
+            } else if score2 > score1 {
+                *runoff.get_mut(second).unwrap() += 1;
+            } else {
+                no_preference += 1;
+            }
+        }
+
+        // finalists are already ordered by total, so only an outright runoff
+        // win moves the second one up
+        if runoff[second] > runoff[first] {
+            standing.swap(0, 1);
+        }
+    }
+
+    let report = StarReport {
+        totals,
+        winner: standing.first().map(|(id, _)| id.clone()),
+        finalists,
+        runoff,
+        no_preference,
+    };
+    (standing, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::fixtures::{marks, nominations, tie_order};
+
+    #[test]
+    fn test_star_runoff_should_beat_higher_total() {
+        let nominations = nominations(&["a", "b", "c"]);
+        let scores = marks(&[
+            &[("a", 5), ("b", 0), ("c", 1)],
+            &[("a", 3), ("b", 4)],
+            &[("a", 3), ("b", 4), ("c", 2)],
+        ]);
+
+        let (standing, report) = star(&nominations, &scores, &tie_order(&["a", "b", "c"]));
+
+        assert_eq!(vec!["a".to_string(), "b".to_string()], report.finalists);
+        assert_eq!(Some(&11), report.totals.get("a"));
+        // "b" is scored above "a" on two ballots out of three
+        assert_eq!(Some(&2), report.runoff.get("b"));
+        assert_eq!(Some("b".to_string()), report.winner);
+        assert_eq!(
+            vec![
+                ("b".to_string(), 8),
+                ("a".to_string(), 11),
+                ("c".to_string(), 3)
+            ],
+            standing
+        );
+    }
+
+    #[test]
+    fn test_star_runoff_tie_should_go_to_higher_total() {
+        let nominations = nominations(&["a", "b"]);
+        let scores = marks(&[&[("a", 5), ("b", 4)], &[("a", 2), ("b", 3)]]);
+
+        let (standing, report) = star(&nominations, &scores, &tie_order(&["a", "b"]));
+
+        assert_eq!(0, report.no_preference);
+        assert_eq!(Some("a".to_string()), report.winner);
+        assert_eq!("a", standing[0].0);
+    }
+
+    #[test]
+    fn test_star_should_not_elect_without_ballots() {
+        let nominations = nominations(&["a", "b"]);
+
+        for scores in [marks::<u8>(&[]), marks(&[&[("z", 5)], &[]])] {
+            let (standing, report) = star(&nominations, &scores, &tie_order(&["a", "b"]));
+
+            assert!(standing.is_empty());
+            assert_eq!(None, report.winner);
+            assert!(report.finalists.is_empty());
+            assert!(report.runoff.is_empty());
+            assert_eq!(Some(&0), report.totals.get("a"));
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::TieOrder;
+use crate::models::{NominationID, Nominations, Rankings};
+
+/// One counting round of a single transferable vote tally.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StvRound {
+    pub round: usize,
+    /// Weighted votes of every continuing nomination.
+    pub tallies: BTreeMap<NominationID, f64>,
+    /// Weight of the ballots with no continuing preference left.
+    pub exhausted: f64,
+    pub elected: Vec<NominationID>,
+    pub eliminated: Option<NominationID>,
+    /// Weight moved to each nomination by this round's surplus transfer or
+    /// elimination.
+    pub transfers: BTreeMap<NominationID, f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StvReport {
+    pub seats: usize,
+    /// Droop quota: floor(ballots / (seats + 1)) + 1.
+    pub quota: usize,
+    /// In the order they were elected.
+    pub elected: Vec<NominationID>,
+    pub rounds: Vec<StvRound>,
+}
+
+struct Ballot<'a> {
+    preferences: &'a [NominationID],
+    weight: f64,
+}
+
+impl<'a> Ballot<'a> {
+    fn top(&self, continuing: &HashSet<&NominationID>) -> Option<&'a NominationID> {
+        self.preferences.iter().find(|id| continuing.contains(id))
+    }
+}
+
+/// Elects `seats` nominations with the single transferable vote, moving
+/// surpluses with fractional (Gregory) transfer values.
+///
+/// Returns the elected nominations in election order followed by the others
+/// from the last one eliminated, each with its last weighted tally, together
+/// with the transfer log. Ties left after first round tallies go by
+/// `tie_order`. Nothing is returned if no ballot ranks a nomination.
+pub fn single_transferable_vote(
+    nominations: &Nominations,
+    rankings: &Rankings,
+    seats: usize,
+    tie_order: &TieOrder,
+) -> (Vec<(NominationID, f64)>, StvReport) {
+    let mut continuing: Vec<NominationID> = nominations.keys().cloned().collect();
+    continuing.sort();
+
+    let mut ballots: Vec<Ballot> = rankings
+        .values()
+        .filter(|ballot| !ballot.is_empty())
+        .map(|ballot| Ballot {
+            preferences: ballot,
+            weight: 1.0,
+        })
+        .collect();
+    let quota = ballots.len() / (seats + 1) + 1;
+
+    let mut rounds: Vec<StvRound> = Vec::new();
+    let mut elected: Vec<(NominationID, f64)> = Vec::new();
+    let mut eliminated: Vec<(NominationID, f64)> = Vec::new();
+    let mut first_tallies: BTreeMap<NominationID, f64> = BTreeMap::new();
+
+    while elected.len() < seats && !continuing.is_empty() {
+        let active: HashSet<&NominationID> = continuing.iter().collect();
+        let mut tallies: BTreeMap<NominationID, f64> =
+            continuing.iter().map(|id| (id.clone(), 0.0)).collect();
+        let mut exhausted = 0.0;
+        for ballot in ballots.iter() {
+            match ballot.top(&active) {
+                Some(id) => *tallies.get_mut(id).unwrap() += ballot.weight,
+                None => exhausted += ballot.weight,
+            }
+        }
+        if rounds.is_empty() {
+            first_tallies = tallies.clone();
+        }
+        let round = rounds.len() + 1;
+
+        // nothing to count, nobody is elected with no votes
+        if round == 1 && tallies.values().all(|tally| *tally == 0.0) {
+            rounds.push(StvRound {
+                round,
+                tallies,
+                exhausted,
+                elected: Vec::new(),
+                eliminated: None,
+                transfers: BTreeMap::new(),
+            });
+            let report = StvReport {
+                seats,
+                quota,
+                elected: Vec::new(),
+                rounds,
+            };
+            return (Vec::new(), report);
+        }
+
+        // as many nominations left as open seats, they are all elected
+        if continuing.len() <= seats - elected.len() {
+            let mut rest: Vec<(NominationID, f64)> = tallies.clone().into_iter().collect();
+            rest.sort_by(|(id1, tally1), (id2, tally2)| {
+                tally2
+                    .total_cmp(tally1)
+                    .then(tie_order[id1].cmp(&tie_order[id2]))
+            });
+            rounds.push(StvRound {
+                round,
+                tallies,
+                exhausted,
+                elected: rest.iter().map(|(id, _)| id.clone()).collect(),
+                eliminated: None,
+                transfers: BTreeMap::new(),
+            });
+            elected.extend(rest);
+            continuing.clear();
+            break;
+        }
+
+        let (leader, leader_tally) = tallies
+            .iter()
+            .max_by(|(id1, tally1), (id2, tally2)| {
+                tally1
+                    .total_cmp(tally2)
+                    .then(tie_order[*id2].cmp(&tie_order[*id1]))
+            })
+            .map(|(id, tally)| (id.clone(), *tally))
+            .unwrap();
+
+        let mut transfers: BTreeMap<NominationID, f64> = BTreeMap::new();
+        let mut move_ballots = |from: &NominationID, transfer_value: f64| {
+            for ballot in ballots.iter_mut() {
+                if ballot.top(&active) != Some(from) {
+                    continue;
+                }
+                ballot.weight *= transfer_value;
+                let next = ballot
+                    .preferences
+                    .iter()
+                    .find(|id| *id != from && active.contains(id));
+                if let Some(next) = next {
+                    *transfers.entry(next.clone()).or_insert(0.0) += ballot.weight;
+                }
+            }
+        };
+
+        if leader_tally >= quota as f64 {
+            // surplus goes on at a reduced value
+            let transfer_value = (leader_tally - quota as f64) / leader_tally;
+            move_ballots(&leader, transfer_value);
+            rounds.push(StvRound {
+                round,
+                tallies,
+                exhausted,
+                elected: vec![leader.clone()],
+                eliminated: None,
+                transfers,
+            });
+            continuing.retain(|id| id != &leader);
+            elected.push((leader, leader_tally));
+            continue;
+        }
+
+        // eliminate the lowest tally, lowest first round tally on a tie, then
+        // the last in tie order
+        let (loser, loser_tally) = tallies
+            .iter()
+            .min_by(|(id1, tally1), (id2, tally2)| {
+                tally1
+                    .total_cmp(tally2)
+                    .then(first_tallies[*id1].total_cmp(&first_tallies[*id2]))
+                    .then(tie_order[*id2].cmp(&tie_order[*id1]))
+            })
+            .map(|(id, tally)| (id.clone(), *tally))
+            .unwrap();
+        move_ballots(&loser, 1.0);
+        rounds.push(StvRound {
+            round,
+            tallies,
+            exhausted,
+            elected: Vec::new(),
+            eliminated: Some(loser.clone()),
+            transfers,
+        });
+        continuing.retain(|id| id != &loser);
+        eliminated.push((loser, loser_tally));
+    }
+
+    let report = StvReport {
+        seats,
+        quota,
+        elected: elected.iter().map(|(id, _)| id.clone()).collect(),
+        rounds,
+    };
+
+    // seats filled before every nomination was counted out
+    let mut standing = elected;
+    let last_tallies = report
+        .rounds
+        .last()
+        .map(|round| round.tallies.clone())
+        .unwrap_or_default();
+    let mut rest: Vec<(NominationID, f64)> = continuing
+        .into_iter()
+        .map(|id| {
+            let tally = last_tallies.get(&id).copied().unwrap_or(0.0);
+            (id, tally)
+        })
+        .collect();
+    rest.sort_by(|(id1, tally1), (id2, tally2)| {
+        tally2
+            .total_cmp(tally1)
+            .then(tie_order[id1].cmp(&tie_order[id2]))
+    });
+    standing.extend(rest);
+    standing.extend(eliminated.into_iter().rev());
+
+    (standing, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::fixtures::{ballots, nominations, tie_order};
+
+    #[test]
+    fn test_stv_should_transfer_surplus() {
+        let nominations = nominations(&["a", "b", "c", "d"]);
+        let rankings = ballots(&[
+            &["a", "b"],
+            &["a", "b"],
+            &["a", "b"],
+            &["a", "b"],
+            &["c"],
+            &["c"],
+            &["d"],
+            &["b"],
+            &["c", "d"],
+        ]);
+
+        let (standing, report) = single_transferable_vote(
+            &nominations,
+            &rankings,
+            2,
+            &tie_order(&["a", "b", "c", "d"]),
+        );
+
+        // 9 ballots, 2 seats
+        assert_eq!(4, report.quota);
+        assert_eq!(vec!["a".to_string()], report.rounds[0].elected);
+        // "a" reached the quota exactly, nothing to transfer
+        assert_eq!(Some(&0.0), report.rounds[0].transfers.get("b"));
+        // "b" and "d" tie all the way, "d" is last in tie order
+        assert_eq!(Some("d".to_string()), report.rounds[1].eliminated);
+        assert_eq!(vec!["a".to_string(), "c".to_string()], report.elected);
+        assert_eq!(4, standing.len());
+    }
+
+    #[test]
+    fn test_stv_should_elect_remaining() {
+        let nominations = nominations(&["a", "b"]);
+        let rankings = ballots(&[&["a"], &["b"], &["a"]]);
+
+        let (standing, report) =
+            single_transferable_vote(&nominations, &rankings, 2, &tie_order(&["a", "b"]));
+
+        assert_eq!(1, report.rounds.len());
+        assert_eq!(vec!["a".to_string(), "b".to_string()], report.elected);
+        assert_eq!(("a".to_string(), 2.0), standing[0]);
+    }
+
+    #[test]
+    fn test_stv_should_not_elect_without_ballots() {
+        let nominations = nominations(&["a", "b", "c"]);
+
+        for rankings in [ballots(&[]), ballots(&[&["z"], &[]])] {
+            let (standing, report) =
+                single_transferable_vote(&nominations, &rankings, 3, &tie_order(&["a", "b", "c"]));
+
+            assert!(standing.is_empty());
+            assert!(report.elected.is_empty());
+            assert_eq!(1, report.rounds.len());
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::NominationID;
+
+/// How nominations with the same score are ordered in the results.
+///
+/// Tallies counting in rounds (instant-runoff, single transferable vote and
+/// STAR) must settle ties as they count, so they can not keep nominations
+/// tied.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreak {
+    /// Tied nominations share their rank.
+    #[default]
+    KeepTied,
+    /// Most first places first: ballots ranking the nomination first or
+    /// giving it their highest mark.
+    FirstPlaces,
+    EarliestNomination,
+    /// A draw seeded with `Poll.tie_break_seed`, so it can be replayed.
+    Random,
+}
+
+/// Where the poll's tie-break puts each nomination, 0 first, for tallies
+/// that settle ties as they count.
+pub type TieOrder = HashMap<NominationID, usize>;
+
+/// A nomination's place in the results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placing {
+    pub nomination_id: NominationID,
+    pub score: f64,
+    /// 1 based, shared by tied nominations.
+    pub rank: usize,
+    pub tied: bool,
+}
+
+/// Sorts `standing` by descending score and ranks it. Nominations with the
+/// same score are ordered by `key`, and stay tied if their keys are equal
+/// too.
+pub fn rank<K, F>(standing: Vec<(NominationID, f64)>, key: F) -> Vec<Placing>
+where
+    K: Ord,
+    F: Fn(&NominationID) -> K,
+{
+    let mut standing: Vec<(NominationID, f64, K)> = standing
+        .into_iter()
+        .map(|(id, score)| {
+            let key = key(&id);
+            (id, score, key)
+        })
+        .collect();
+    standing.sort_by(|(id1, score1, key1), (id2, score2, key2)| {
+        score2
+            .total_cmp(score1)
+            .then(key1.cmp(key2))
+            .then(id1.cmp(id2))
+    });
+
+    let same = |i: usize, j: usize| {
+        standing[i].1.total_cmp(&standing[j].1) == Ordering::Equal && standing[i].2 == standing[j].2
+    };
+    let mut placings: Vec<Placing> = Vec::with_capacity(standing.len());
+    for i in 0..standing.len() {
+        let rank = match i {
+            0 => 1,
+            _ if same(i - 1, i) => placings[i - 1].rank,
+            _ => i + 1,
+        };
+        let tied = (i > 0 && same(i - 1, i)) || (i + 1 < standing.len() && same(i, i + 1));
+        placings.push(Placing {
+            nomination_id: standing[i].0.clone(),
+            score: standing[i].1,
+            rank,
+            tied,
+        });
+    }
+    placings
+}
+
+/// Position of a nomination in the draw seeded with `seed`.
+///
+/// Hand rolled (FNV-1a then splitmix64) rather than taken from `rand`, so a
+/// recorded seed replays the same draw whatever version built the server.
+pub fn draw(seed: u64, nomination_id: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in nomination_id.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    let mut z = (seed ^ hash).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Writes a seed as a string, since JSON numbers above 2^53 lose precision
+/// in RedisJSON and in browsers, and the draw would not replay. Seeds written
+/// as numbers are still read.
+pub mod seed_as_string {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Seed {
+        String(String),
+        Number(u64),
+    }
+
+    pub fn serialize<S: Serializer>(seed: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match seed {
+            Some(seed) => serializer.serialize_some(&seed.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        match Option::<Seed>::deserialize(deserializer)? {
+            Some(Seed::String(seed)) => seed.parse().map(Some).map_err(de::Error::custom),
+            Some(Seed::Number(seed)) => Ok(Some(seed)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standing(scores: &[(&str, f64)]) -> Vec<(NominationID, f64)> {
+        scores
+            .iter()
+            .map(|(id, score)| (id.to_string(), *score))
+            .collect()
+    }
+
+    #[test]
+    fn test_rank_should_share_tied_ranks() {
+        let placings = rank(standing(&[("c", 1.0), ("b", 3.0), ("a", 3.0)]), |_| ());
+
+        let ranks: Vec<(&str, usize, bool)> = placings
+            .iter()
+            .map(|p| (p.nomination_id.as_str(), p.rank, p.tied))
+            .collect();
+        assert_eq!(vec![("a", 1, true), ("b", 1, true), ("c", 3, false)], ranks);
+    }
+
+    #[test]
+    fn test_rank_should_break_ties_by_key() {
+        let placings = rank(standing(&[("a", 3.0), ("b", 3.0)]), |id| {
+            if id == "b" {
+                0
+            } else {
+                1
+            }
+        });
+
+        assert_eq!("b", placings[0].nomination_id);
+        assert_eq!(2, placings[1].rank);
+        assert!(!placings[0].tied);
+    }
+
+    #[test]
+    fn test_draw_should_replay_with_seed() {
+        assert_eq!(draw(42, "a"), draw(42, "a"));
+        assert_ne!(draw(42, "a"), draw(43, "a"));
+    }
+
+    #[test]
+    fn test_seed_as_string_should_keep_every_bit() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Seeded {
+            #[serde(default, with = "seed_as_string")]
+            seed: Option<u64>,
+        }
+
+        let seeded = Seeded {
+            seed: Some(u64::MAX),
+        };
+        let json = serde_json::to_string(&seeded).unwrap();
+        assert_eq!(r#"{"seed":"18446744073709551615"}"#, json);
+        assert_eq!(seeded, serde_json::from_str(&json).unwrap());
+
+        let seeded: Seeded = serde_json::from_str(r#"{"seed":42}"#).unwrap();
+        assert_eq!(Some(42), seeded.seed);
+        let seeded: Seeded = serde_json::from_str("{}").unwrap();
+        assert_eq!(None, seeded.seed);
+    }
+}
@@ -16,6 +16,14 @@ pub fn service(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", post(polls::add))
         .route("/join", post(polls::join))
-        .route("/rejoin", post(polls::rejoin).route_layer(middleware_stack))
+        .route("/join/invite", post(polls::join_with_invite))
+        .route("/spectate", post(polls::spectate))
+        .merge(
+            Router::new()
+                .route("/rejoin", post(polls::rejoin))
+                .route("/invites", post(polls::add_invite))
+                .route("/invites/revoke", post(polls::revoke_invite))
+                .route_layer(middleware_stack),
+        )
         .with_state(state)
 }
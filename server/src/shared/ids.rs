@@ -16,3 +16,11 @@ pub fn create_user_id() -> String {
 pub fn create_nomination_id() -> String {
     nanoid!(8)
 }
+
+pub fn create_invite_id() -> String {
+    nanoid!(10)
+}
+
+pub fn create_connection_id() -> String {
+    nanoid!()
+}
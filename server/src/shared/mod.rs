@@ -1 +1,3 @@
 pub mod ids;
+pub mod passphrase;
+pub mod throttle;
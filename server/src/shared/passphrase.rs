@@ -0,0 +1,50 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand::rngs::OsRng;
+
+use crate::errors::Error;
+
+/// Hashes a poll passphrase into a PHC string, salt and parameters included.
+///
+/// Argon2 is slow on purpose, so it runs on the blocking pool.
+pub async fn hash(passphrase: String) -> Result<String, Error> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(passphrase.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|_| Error::PassphraseHashing)
+    })
+    .await
+    .map_err(|_| Error::PassphraseHashing)?
+}
+
+/// Checks `passphrase` against a hash made by `hash`.
+pub async fn verify(passphrase: String, hash: String) -> Result<bool, Error> {
+    tokio::task::spawn_blocking(move || {
+        let hash = PasswordHash::new(&hash).map_err(|_| Error::PassphraseHashing)?;
+        Ok(Argon2::default()
+            .verify_password(passphrase.as_bytes(), &hash)
+            .is_ok())
+    })
+    .await
+    .map_err(|_| Error::PassphraseHashing)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_verify_should_match_hashed_passphrase() {
+        let hash = hash("open sesame".to_string()).await.unwrap();
+
+        assert!(!hash.contains("open sesame"));
+        assert!(verify("open sesame".to_string(), hash.clone())
+            .await
+            .unwrap());
+        assert!(!verify("open sesame!".to_string(), hash).await.unwrap());
+    }
+}
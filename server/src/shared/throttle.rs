@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::errors::Error;
+
+/// Entries kept before expired ones are dropped.
+const PRUNE_ABOVE: usize = 1024;
+
+#[derive(Debug)]
+struct Attempts {
+    count: usize,
+    since: Instant,
+}
+
+/// Counts attempts per key, such as a poll or an address, and refuses more
+/// once a key reaches its limit, until its window is over. Attempts are
+/// counted before they are made, so concurrent ones can not get past the
+/// limit, and refunded if they succeed.
+#[derive(Debug)]
+pub struct Throttle {
+    attempts: DashMap<String, Attempts>,
+    window: Duration,
+}
+
+impl Throttle {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            attempts: DashMap::new(),
+            window,
+        }
+    }
+
+    /// Counts an attempt for `key`, starting a new window if the last one
+    /// is over. Fails with `Error::TooManyAttempts`, without counting it, if
+    /// `key` already has `max` attempts in the current window.
+    pub fn try_acquire(&self, key: &str, max: usize) -> Result<(), Error> {
+        if self.attempts.len() > PRUNE_ABOVE {
+            self.attempts
+                .retain(|_, attempts| attempts.since.elapsed() < self.window);
+        }
+        let mut attempts = self.attempts.entry(key.to_string()).or_insert(Attempts {
+            count: 0,
+            since: Instant::now(),
+        });
+        if attempts.since.elapsed() >= self.window {
+            *attempts = Attempts {
+                count: 0,
+                since: Instant::now(),
+            };
+        }
+        if attempts.count >= max {
+            return Err(Error::TooManyAttempts);
+        }
+        attempts.count += 1;
+        Ok(())
+    }
+
+    /// Gives back an attempt counted by `Throttle::try_acquire`, once it
+    /// turned out not to be a failure.
+    pub fn refund(&self, key: &str) {
+        if let Some(mut attempts) = self.attempts.get_mut(key) {
+            attempts.count = attempts.count.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_acquire_should_refuse_after_max_attempts() {
+        let throttle = Throttle::new(Duration::from_secs(60));
+        assert_eq!(Ok(()), throttle.try_acquire("a", 2));
+        assert_eq!(Ok(()), throttle.try_acquire("a", 2));
+
+        assert_eq!(Err(Error::TooManyAttempts), throttle.try_acquire("a", 2));
+        assert_eq!(Ok(()), throttle.try_acquire("b", 2));
+    }
+
+    #[test]
+    fn test_refund_should_give_back_attempt() {
+        let throttle = Throttle::new(Duration::from_secs(60));
+        throttle.try_acquire("a", 1).unwrap();
+        throttle.refund("a");
+
+        assert_eq!(Ok(()), throttle.try_acquire("a", 1));
+    }
+
+    #[test]
+    fn test_try_acquire_should_forget_attempts_after_window() {
+        let throttle = Throttle::new(Duration::ZERO);
+        throttle.try_acquire("a", 1).unwrap();
+
+        assert_eq!(Ok(()), throttle.try_acquire("a", 1));
+    }
+}
@@ -3,16 +3,78 @@ use std::sync::Arc;
 use serde::Deserialize;
 use tokio::sync::broadcast;
 
-use crate::models::{room::Rooms, Notification};
+use crate::{
+    models::{room::Rooms, Notification},
+    shared::throttle::Throttle,
+};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct EnvConfig {
     pub server_http_port: u16,
     pub client_domain: String,
     pub client_port: u16,
+    #[serde(default)]
+    pub store: StoreKind,
+    /// Only required by `StoreKind::Redis`.
+    #[serde(default)]
     pub redis_url: String,
+    /// Database file of `StoreKind::Embedded`.
+    #[serde(default = "default_embedded_path")]
+    pub embedded_path: String,
     pub poll_duration: usize,
     pub jwt_secret: String,
+    /// Seconds the admin can be disconnected before someone else is made
+    /// admin, 0 to never do it.
+    #[serde(default = "default_admin_timeout")]
+    pub admin_timeout: u64,
+    /// Wrong passphrases an address can send before it is throttled.
+    #[serde(default = "default_join_failures_per_ip")]
+    pub join_failures_per_ip: usize,
+    /// Wrong passphrases a poll can be sent, from anywhere, before joining
+    /// it is slowed down.
+    #[serde(default = "default_join_failures_per_poll")]
+    pub join_failures_per_poll: usize,
+    /// Milliseconds joins of a poll past `join_failures_per_poll` are
+    /// delayed by.
+    #[serde(default = "default_join_failure_delay")]
+    pub join_failure_delay: u64,
+    /// Seconds failed joins are counted over.
+    #[serde(default = "default_join_failure_window")]
+    pub join_failure_window: u64,
+}
+
+/// Poll storage backend, selected with `RANKER_STORE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreKind {
+    #[default]
+    Redis,
+    Memory,
+    Embedded,
+}
+
+fn default_embedded_path() -> String {
+    "ranker.redb".to_string()
+}
+
+fn default_admin_timeout() -> u64 {
+    60
+}
+
+fn default_join_failures_per_ip() -> usize {
+    5
+}
+
+fn default_join_failures_per_poll() -> usize {
+    20
+}
+
+fn default_join_failure_delay() -> u64 {
+    1000
+}
+
+fn default_join_failure_window() -> u64 {
+    300
 }
 
 #[derive(Debug, Clone)]
@@ -24,4 +86,7 @@ pub struct AppState {
     pub notify_tx: broadcast::Sender<String>,
 
     pub sse_tx: broadcast::Sender<Notification>,
+
+    /// Failed joins of passphrase protected polls.
+    pub join_throttle: Arc<Throttle>,
 }
@@ -0,0 +1,48 @@
+use crate::{
+    data::PollStore,
+    models::{Nomination, Poll},
+};
+
+/// Id of the poll made by `poll`.
+pub const POLL_ID: &str = "ABCDEF";
+
+/// A poll administered by `u1`, to store as it is or with the fields a test
+/// needs set first.
+pub fn poll() -> Poll {
+    Poll::new(
+        POLL_ID.to_string(),
+        "topic".to_string(),
+        1,
+        "u1".to_string(),
+    )
+}
+
+/// Stores `poll`, joined by `participants`, named after their ids, and opens
+/// its nominations with one nomination, `n1`.
+pub async fn nominating_poll(store: &dyn PollStore, poll: Poll, participants: &[&str]) -> Poll {
+    let poll_id = poll.id.clone();
+    store.add_poll(60, poll).await.unwrap();
+    for user_id in participants {
+        store
+            .add_participant(poll_id.clone(), user_id.to_string(), user_id.to_string())
+            .await
+            .unwrap();
+    }
+    store.open_nominations(poll_id.clone()).await.unwrap();
+    let nomination = Nomination {
+        text: "text".to_string(),
+        user_id: "u1".to_string(),
+        created_at: 0,
+    };
+    store
+        .add_nomination(poll_id, "n1".to_string(), nomination)
+        .await
+        .unwrap()
+}
+
+/// Stores `poll` as `nominating_poll` does, and starts voting on it.
+pub async fn voting_poll(store: &dyn PollStore, poll: Poll, participants: &[&str]) -> Poll {
+    let poll_id = poll.id.clone();
+    nominating_poll(store, poll, participants).await;
+    store.start_poll(poll_id, None).await.unwrap()
+}
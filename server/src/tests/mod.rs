@@ -1 +1,2 @@
+pub mod fixtures;
 mod polls;
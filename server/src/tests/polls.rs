@@ -1,17 +1,251 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
 
-use crate::data::redis::polls::{
-    add_nomination, add_participant, add_poll, add_results, del_poll, get_poll, remove_nomination,
-    remove_participant, start_poll,
+use crate::data::{
+    embedded::EmbeddedPollStore, memory::MemoryPollStore, redis::RedisPollStore, PollStore,
 };
-use crate::models::{Nomination, Poll, Result};
+use crate::errors::Error;
+use crate::handlers::{
+    polls::check_joinable,
+    schedule::close_at_deadline,
+    ws::{
+        close_if_all_voted, reconnect, remove_participant, submit_allocations, submit_approvals,
+        submit_scores,
+    },
+};
+use crate::models::{room::Rooms, BallotType, Nomination, Poll, PollPhase, Quorum, Result};
+use crate::tests::fixtures::{nominating_poll, poll, voting_poll, POLL_ID};
 
 #[tokio::test]
 async fn test_polls_lifecycle() {
-    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-    let mut con = redis::aio::ConnectionManager::new(client).await.unwrap();
+    let store = MemoryPollStore::new();
+    polls_lifecycle(&store).await
+}
+
+#[tokio::test]
+async fn test_embedded_polls_lifecycle() {
+    let path = std::env::temp_dir().join(format!("ranker-{}.redb", uuid::Uuid::new_v4()));
+    let store = EmbeddedPollStore::open(&path).unwrap();
+    polls_lifecycle(&store).await;
+    let _ = std::fs::remove_file(path);
+}
+
+/// Runs against the RedisJSON server at `RANKER_TEST_REDIS_URL`, which
+/// `make test/integration/redis` points at a local one, and is skipped
+/// without it.
+#[tokio::test]
+async fn test_redis_polls_lifecycle() {
+    let Ok(redis_url) = std::env::var("RANKER_TEST_REDIS_URL") else {
+        eprintln!("skipped: RANKER_TEST_REDIS_URL is not set");
+        return;
+    };
+    let client = redis::Client::open(redis_url).unwrap();
+    let store = RedisPollStore::new(client).await.unwrap();
+    polls_lifecycle(&store).await
+}
+
+#[tokio::test]
+async fn test_reconnect_should_only_let_participants_read_closed_poll() {
+    let store = MemoryPollStore::new();
+    voting_poll(&store, poll(), &["u1"]).await;
+    store
+        .add_results(POLL_ID.to_string(), Vec::new(), None, false)
+        .await
+        .unwrap();
+
+    let poll = reconnect(
+        &store,
+        POLL_ID.to_string(),
+        "u1".to_string(),
+        "ben".to_string(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(PollPhase::Closed, poll.phase);
+
+    let Err(err) = reconnect(
+        &store,
+        POLL_ID.to_string(),
+        "u2".to_string(),
+        "ann".to_string(),
+    )
+    .await
+    else {
+        panic!("Should be got an error but not")
+    };
+    assert_eq!(Error::InvalidPhase, err);
+}
+
+#[tokio::test]
+async fn test_check_joinable_should_refuse_closed_poll() {
+    let store = MemoryPollStore::new();
+    assert_eq!(Ok(()), check_joinable(&poll(), None));
+
+    voting_poll(&store, poll(), &[]).await;
+    let poll = store
+        .add_results(POLL_ID.to_string(), Vec::new(), None, false)
+        .await
+        .unwrap();
+
+    assert_eq!(Err(Error::InvalidPhase), check_joinable(&poll, None));
+}
+
+#[tokio::test]
+async fn test_close_if_all_voted_should_close_on_last_ballot() {
+    let store = MemoryPollStore::new();
+    let poll = Poll {
+        close_when_all_voted: true,
+        ..poll()
+    };
+    voting_poll(&store, poll, &["u1", "u2"]).await;
+
+    let poll = store
+        .add_participant_rankings(
+            POLL_ID.to_string(),
+            "u1".to_string(),
+            vec!["n1".to_string()],
+        )
+        .await
+        .unwrap();
+    let poll = close_if_all_voted(&store, poll).await;
+    assert_eq!(PollPhase::Voting, poll.phase);
+
+    let poll = store
+        .add_participant_rankings(
+            POLL_ID.to_string(),
+            "u2".to_string(),
+            vec!["n1".to_string()],
+        )
+        .await
+        .unwrap();
+    let poll = close_if_all_voted(&store, poll).await;
+    assert_eq!(PollPhase::Closed, poll.phase);
+    assert_eq!("n1", poll.results[0].nomination_id);
+    assert_eq!(poll, store.get_poll(POLL_ID.to_string()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_remove_participant_should_close_once_everyone_left_voted() {
+    let store = MemoryPollStore::new();
+    let poll = Poll {
+        close_when_all_voted: true,
+        ..poll()
+    };
+    voting_poll(&store, poll, &["u1", "u2"]).await;
+    let poll = store
+        .add_participant_rankings(
+            POLL_ID.to_string(),
+            "u1".to_string(),
+            vec!["n1".to_string()],
+        )
+        .await
+        .unwrap();
+    let poll = close_if_all_voted(&store, poll).await;
+    assert_eq!(PollPhase::Voting, poll.phase);
+
+    let poll = remove_participant(
+        &store,
+        POLL_ID.to_string(),
+        "u1".to_string(),
+        Arc::new(Rooms::default()),
+        "u2".to_string(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(PollPhase::Closed, poll.phase);
+    assert_eq!(poll, store.get_poll(POLL_ID.to_string()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_close_at_deadline_should_leave_poll_open_below_quorum() {
+    let store = MemoryPollStore::new();
+    let poll = Poll {
+        quorum: Some(Quorum::Voters(2)),
+        ..poll()
+    };
+    nominating_poll(&store, poll, &["u1"]).await;
+    store
+        .start_poll(POLL_ID.to_string(), Some(0))
+        .await
+        .unwrap();
+
+    let poll = close_at_deadline(&store, &Rooms::default(), POLL_ID.to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(PollPhase::Voting, poll.phase);
+    assert_eq!(None, poll.closes_at);
+    assert!(store.deadlines().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_submit_approvals_should_refuse_empty_ballot() {
+    let store = MemoryPollStore::new();
+    let poll = Poll {
+        ballot_type: BallotType::Approval,
+        ..poll()
+    };
+    voting_poll(&store, poll, &["u1"]).await;
+
+    let Err(err) =
+        submit_approvals(&store, POLL_ID.to_string(), "u1".to_string(), Vec::new()).await
+    else {
+        panic!("Should be got an error but not")
+    };
+    assert_eq!(Error::EmptyBallot, err);
+    let poll = store.get_poll(POLL_ID.to_string()).await.unwrap();
+    assert!(poll.approvals.is_empty());
+}
+
+#[tokio::test]
+async fn test_submit_scores_should_refuse_empty_ballot() {
+    let store = MemoryPollStore::new();
+    let poll = Poll {
+        ballot_type: BallotType::Score,
+        ..poll()
+    };
+    voting_poll(&store, poll, &["u1"]).await;
+
+    let Err(err) = submit_scores(
+        &store,
+        POLL_ID.to_string(),
+        "u1".to_string(),
+        HashMap::new(),
+    )
+    .await
+    else {
+        panic!("Should be got an error but not")
+    };
+    assert_eq!(Error::EmptyBallot, err);
+    let poll = store.get_poll(POLL_ID.to_string()).await.unwrap();
+    assert!(poll.scores.is_empty());
+}
+
+#[tokio::test]
+async fn test_submit_allocations_should_refuse_empty_ballot() {
+    let store = MemoryPollStore::new();
+    let poll = Poll {
+        ballot_type: BallotType::Cumulative,
+        ..poll()
+    };
+    voting_poll(&store, poll, &["u1"]).await;
+
+    for allocations in [HashMap::new(), HashMap::from([("n1".to_string(), 0)])] {
+        let Err(err) =
+            submit_allocations(&store, POLL_ID.to_string(), "u1".to_string(), allocations).await
+        else {
+            panic!("Should be got an error but not")
+        };
+        assert_eq!(Error::EmptyBallot, err);
+    }
+    let poll = store.get_poll(POLL_ID.to_string()).await.unwrap();
+    assert!(poll.allocations.is_empty());
+}
+
+async fn polls_lifecycle(store: &dyn PollStore) {
     let ttl = 1; // expire 1s
     let poll_id = "iBOY-JBDILBW3aWQwFTES".to_string();
     let user_id = "T7EYUQ".to_string();
@@ -26,54 +260,37 @@ async fn test_polls_lifecycle() {
     );
 
     // 1.add poll
-    let adding_poll = add_poll(
-        &mut con,
-        ttl,
-        poll_id.clone(),
-        topic.clone(),
-        votes_per_voter,
-        user_id.clone(),
-    )
-    .await
-    .unwrap();
+    let adding_poll = store.add_poll(ttl, poll.clone()).await.unwrap();
     assert_eq!(poll, adding_poll);
 
     // 2.get poll
-    let first_get_poll = get_poll(&mut con, poll_id.clone()).await.unwrap();
+    let first_get_poll = store.get_poll(poll_id.clone()).await.unwrap();
     assert_eq!(poll, first_get_poll);
 
     // 3.add participant
-    let add_participant_poll =
-        add_participant(&mut con, poll_id.clone(), user_id.clone(), name.clone())
-            .await
-            .unwrap();
+    let add_participant_poll = store
+        .add_participant(poll_id.clone(), user_id.clone(), name.clone())
+        .await
+        .unwrap();
     let mut expect_add_participant_poll = poll.clone();
     expect_add_participant_poll.participants =
         HashMap::from([(poll.admin_id.clone(), name.clone())]);
     assert_eq!(expect_add_participant_poll, add_participant_poll);
 
     // 4.remove participant
-    let remove_participant_poll = remove_participant(
-        &mut con,
-        add_participant_poll.id,
-        add_participant_poll.admin_id,
-    )
-    .await
-    .unwrap();
+    let remove_participant_poll = store
+        .remove_participant(add_participant_poll.id, add_participant_poll.admin_id)
+        .await
+        .unwrap();
     let mut expect_remove_participant_poll = expect_add_participant_poll.clone();
     expect_remove_participant_poll.participants = HashMap::new();
     assert_eq!(expect_remove_participant_poll, remove_participant_poll);
 
-    // 5.start poll
-    let started_poll = start_poll(&mut con, poll_id.clone()).await.unwrap();
-    let mut expect_started_poll = expect_remove_participant_poll.clone();
-    expect_started_poll.has_started = true;
-    assert_eq!(expect_started_poll, started_poll);
-
-    // remove participant get error(poll has started)
-    let Err(_) = remove_participant(&mut con, expect_started_poll.id.clone(), expect_started_poll.admin_id.clone()).await else {
-        panic!("Should be got an error but not")
-    };
+    // 5.open nominations
+    let opened_poll = store.open_nominations(poll_id.clone()).await.unwrap();
+    let mut expect_opened_poll = expect_remove_participant_poll.clone();
+    expect_opened_poll.phase = PollPhase::Nominating;
+    assert_eq!(expect_opened_poll, opened_poll);
 
     // 6.add nomination
     let nomination_id = "nominati".to_string();
@@ -81,62 +298,84 @@ async fn test_polls_lifecycle() {
     let nomination = Nomination {
         user_id: user_id.clone(),
         text: text.clone(),
+        created_at: 0,
     };
-    let add_nomination_poll = add_nomination(
-        &mut con,
-        poll_id.clone(),
-        nomination_id.clone(),
-        nomination.clone(),
-    )
-    .await
-    .unwrap();
-    let mut expect_add_nomination_poll = expect_started_poll.clone();
+    let add_nomination_poll = store
+        .add_nomination(poll_id.clone(), nomination_id.clone(), nomination.clone())
+        .await
+        .unwrap();
+    let mut expect_add_nomination_poll = expect_opened_poll.clone();
     expect_add_nomination_poll.nominations = HashMap::from([(nomination_id.clone(), nomination)]);
     assert_eq!(expect_add_nomination_poll, add_nomination_poll);
 
     // 7.remove nomination
-    let remove_nomination_poll =
-        remove_nomination(&mut con, poll_id.clone(), nomination_id.clone())
-            .await
-            .unwrap();
+    let remove_nomination_poll = store
+        .remove_nomination(poll_id.clone(), nomination_id.clone())
+        .await
+        .unwrap();
 
     let mut expect_remove_nomination_poll = expect_add_nomination_poll.clone();
     expect_remove_nomination_poll.nominations = HashMap::new();
     assert_eq!(expect_remove_nomination_poll, remove_nomination_poll);
 
-    // 8.add results
+    // 8.start poll
+    let started_poll = store.start_poll(poll_id.clone(), None).await.unwrap();
+    let mut expect_started_poll = expect_remove_nomination_poll.clone();
+    expect_started_poll.phase = PollPhase::Voting;
+    assert_eq!(expect_started_poll, started_poll);
+
+    // remove participant get error(poll has started)
+    let Err(err) = store
+        .remove_participant(
+            expect_started_poll.id.clone(),
+            expect_started_poll.admin_id.clone(),
+        )
+        .await
+    else {
+        panic!("Should be got an error but not")
+    };
+    assert_eq!(Error::InvalidPhase, err);
+
+    // 9.add results
     let results = vec![Result {
         nomination_id,
         nomination_text: text,
-        score: "1".to_string(),
+        score: 1.0,
+        rank: 1,
+        tied: false,
+        histogram: vec![1],
+        ballots: 1,
+        share: Some(1.0),
     }];
-    let add_results_poll = add_results(&mut con, poll_id.clone(), results.clone())
+    let add_results_poll = store
+        .add_results(poll_id.clone(), results.clone(), None, false)
         .await
         .unwrap();
-    let mut expect_add_results_poll = expect_remove_nomination_poll;
+    let mut expect_add_results_poll = expect_started_poll;
     expect_add_results_poll.results = results;
+    expect_add_results_poll.phase = PollPhase::Closed;
     assert_eq!(expect_add_results_poll, add_results_poll);
 
-    // 9.remove poll
-    del_poll(&mut con, poll_id.clone()).await.unwrap();
-    let Err(_) = get_poll(&mut con, poll_id.clone()).await else {
+    // no one joins a closed poll
+    let Err(err) = store
+        .add_participant(poll_id.clone(), user_id.clone(), name.clone())
+        .await
+    else {
         panic!("Should be got an error but not")
     };
+    assert_eq!(Error::InvalidPhase, err);
 
-    // 10.wait for expired
-    let _ = add_poll(
-        &mut con,
-        ttl,
-        poll_id.clone(),
-        topic,
-        votes_per_voter,
-        user_id.clone(),
-    )
-    .await
-    .unwrap();
+    // 10.remove poll
+    store.del_poll(poll_id.clone()).await.unwrap();
+    let Err(_) = store.get_poll(poll_id.clone()).await else {
+        panic!("Should be got an error but not")
+    };
+
+    // 11.wait for expired
+    let _ = store.add_poll(ttl, poll).await.unwrap();
     sleep(Duration::from_secs(ttl as u64));
 
-    let Err(_) = get_poll(&mut con, poll_id.clone()).await else {
+    let Err(_) = store.get_poll(poll_id.clone()).await else {
         panic!("Should be got an error")
     };
 }